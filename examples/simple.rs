@@ -1,5 +1,5 @@
 use fluxion::{actor, message, Fluxion, Handler, Identifier};
-use palantir::{backend::{Backend, Channel}, ActorID, Palantir};
+use palantir::{backend::{Backend, Channel, ChannelError, OpenChannelError}, ActorID, Palantir, Request};
 use serde::{Deserialize, Serialize};
 
 
@@ -8,19 +8,24 @@ pub struct TestingBackend;
 impl Backend for TestingBackend {
     type Channel = TestingChannel;
 
-    async fn open_channel<M: fluxion::Message>(&self, actor: ActorID, system: &str, message_type: &'static str) -> Option<Self::Channel> {
+    async fn open_channel<M: fluxion::Message>(&self, actor: ActorID, system: &str, _message_type: &str) -> Result<Self::Channel, OpenChannelError> {
         
         println!("Opening dummy channel for {:?}/{}", actor, system);
-        Some(TestingChannel(actor, system.to_string()))
+        Ok(TestingChannel(actor, system.to_string()))
+    }
+
+    async fn incoming(&self) -> Option<(ActorID, String, Request)> {
+        // The dummy backend never receives anything
+        None
     }
 }
 
 pub struct TestingChannel(ActorID, String);
 
 impl Channel for TestingChannel {
-    async fn request(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+    async fn request(&self, data: Vec<u8>) -> Result<Vec<u8>, ChannelError> {
         println!("Dummy request: {:?}/{} sent: {:?}", self.0, self.1, data);
-        Some(b"hello, world!".to_vec())
+        Ok(b"hello, world!".to_vec())
     }
 }
 
@@ -28,7 +33,7 @@ impl Channel for TestingChannel {
 struct TestActor;
 
 impl Handler<TestMessage> for TestActor {
-    async fn handle_message<D: fluxion::Delegate>(&self, message: TestMessage, context: &fluxion::ActorContext<D>) -> () {
+    async fn handle_message<D: fluxion::Delegate>(&self, _message: TestMessage, _context: &fluxion::ActorContext<D>) -> () {
         println!("test message won't be received");
     }
 }
@@ -40,13 +45,13 @@ struct TestMessage;
 async fn main() {
 
     let backend = TestingBackend;
-    let delegate = Palantir::new("sys1".to_string(), backend);
+    let delegate = Palantir::new("sys1".to_string(), backend).expect("sys1 is a valid system id");
     let system = Fluxion::new("sys1", delegate);
 
     // Open a test on another channel
-    let mh = system.get::<TestActor, _>(Identifier::ForeignNamed("sys2", "testactor")).await.unwrap();
+    let _mh = system.get::<TestActor, _>(Identifier::ForeignNamed("sys2", "testactor")).await.unwrap();
 
-    //mh.send(TestMessage).await;
+    //_mh.send(TestMessage).await;
 
     system.shutdown().await;
 }
\ No newline at end of file
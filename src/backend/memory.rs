@@ -0,0 +1,279 @@
+//! # Memory
+//! Contains [`MemoryBackend`], which connects any number of palantir instances within the same process.
+//! Every backend is created from a shared [`MemoryNetwork`], and can reach every other backend created from it
+//! that still exists. This is mostly useful for testing multi-system setups without a real network.
+
+use std::{collections::HashMap, sync::{Arc, Mutex, PoisonError}};
+
+use fluxion::Message;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{backend::{Backend, Channel, ChannelError, OpenChannelError, PeerEvent}, ActorID, Request};
+
+
+
+/// # [`Inbound`]
+/// The sending half of a system's inbound requests.
+type Inbound = mpsc::UnboundedSender<(ActorID, String, Request)>;
+
+/// # [`MemoryNetwork`]
+/// Connects the [`MemoryBackend`]s created from it. Cloning it returns a handle to the same network.
+/// Every backend is told when others join or leave the network (see [`Backend::peer_event`]).
+#[derive(Clone)]
+pub struct MemoryNetwork {
+    /// The inbound requests of every system on the network
+    systems: Arc<Mutex<HashMap<String, Inbound>>>,
+    /// Tells every backend of systems joining and leaving the network
+    peers: broadcast::Sender<PeerEvent>,
+}
+
+impl Default for MemoryNetwork {
+    fn default() -> Self {
+        Self {
+            systems: Arc::default(),
+            peers: broadcast::channel(256).0,
+        }
+    }
+}
+
+impl MemoryNetwork {
+    /// # [`MemoryNetwork::new`]
+    /// Creates an empty network.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # [`MemoryNetwork::backend`]
+    /// Creates a backend for the given system on this network. If the system already has a backend,
+    /// requests to it are routed to the new one instead.
+    #[must_use]
+    pub fn backend(&self, system: &str) -> MemoryBackend {
+        let (inbound, incoming) = mpsc::unbounded_channel();
+
+        self.systems.lock().unwrap_or_else(PoisonError::into_inner)
+            .insert(system.to_string(), inbound.clone());
+
+        // Subscribe before announcing ourselves, so that we are told of everyone joining after us.
+        // Nobody listening is not an error.
+        let peers = self.peers.subscribe();
+        let _ = self.peers.send(PeerEvent::Connected { system: system.to_string() });
+
+        MemoryBackend {
+            network: self.clone(),
+            system: system.to_string(),
+            inbound,
+            incoming: tokio::sync::Mutex::new(incoming),
+            peers: tokio::sync::Mutex::new(peers),
+        }
+    }
+
+    /// # [`MemoryNetwork::systems`]
+    /// Returns the systems that currently have a backend on this network.
+    #[must_use]
+    pub fn systems(&self) -> Vec<String> {
+        self.systems.lock().unwrap_or_else(PoisonError::into_inner)
+            .keys().cloned().collect()
+    }
+}
+
+/// # [`MemoryBackend`]
+/// A [`Backend`] that connects to the other systems on its [`MemoryNetwork`] via in-process channels.
+/// Dropping it removes its system from the network.
+pub struct MemoryBackend {
+    /// The network this backend is on
+    network: MemoryNetwork,
+    /// This backend's system
+    system: String,
+    /// The sending half of this backend's inbound requests, which identifies it on the network
+    inbound: Inbound,
+    /// Receives this backend's inbound requests
+    incoming: tokio::sync::Mutex<mpsc::UnboundedReceiver<(ActorID, String, Request)>>,
+    /// Receives systems joining and leaving the network
+    peers: tokio::sync::Mutex<broadcast::Receiver<PeerEvent>>,
+}
+
+impl Drop for MemoryBackend {
+    fn drop(&mut self) {
+        let mut systems = self.network.systems.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Only remove the system if it hasn't been replaced by another backend
+        if systems.get(&self.system).is_some_and(|inbound| inbound.same_channel(&self.inbound)) {
+            systems.remove(&self.system);
+            let _ = self.network.peers.send(PeerEvent::Disconnected {
+                system: self.system.clone(),
+                reason: "the backend was dropped".to_string(),
+            });
+        }
+    }
+}
+
+impl Backend for MemoryBackend {
+    type Channel = MemoryChannel;
+
+    async fn open_channel<M: Message>(&self, actor: ActorID, system: &str, message_type: &str) -> Result<Self::Channel, OpenChannelError> {
+
+        let Some(outbound) = self.network.systems.lock().unwrap_or_else(PoisonError::into_inner)
+            .get(system).cloned() else {
+            return Err(OpenChannelError::SystemUnreachable(system.to_string()));
+        };
+
+        Ok(MemoryChannel {
+            source: self.system.clone(),
+            outbound,
+            actor,
+            message_type: message_type.to_string(),
+        })
+    }
+
+    async fn incoming(&self) -> Option<(ActorID, String, Request)> {
+        self.incoming.lock().await.recv().await
+    }
+
+    async fn peer_event(&self) -> Option<PeerEvent> {
+        let mut peers = self.peers.lock().await;
+
+        loop {
+            match peers.recv().await {
+                // We are told about ourselves as well
+                Ok(event) if event.system() == self.system => {},
+                Ok(event) => return Some(event),
+                // Missing some changes is fine, as peers can be enumerated with `systems`
+                Err(broadcast::error::RecvError::Lagged(_)) => {},
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    async fn systems(&self) -> Vec<String> {
+        self.network.systems.lock().unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .filter(|system| **system != self.system)
+            .cloned()
+            .collect()
+    }
+}
+
+/// # [`MemoryChannel`]
+/// The [`Channel`] opened by a [`MemoryBackend`].
+pub struct MemoryChannel {
+    /// The system the channel was opened from, which identifies its requests alongside their ids
+    source: String,
+    /// Sends requests to the system
+    outbound: Inbound,
+    /// The actor the channel is connected to
+    actor: ActorID,
+    /// The channel's message type
+    message_type: String,
+}
+
+impl MemoryChannel {
+    /// # [`MemoryChannel::send`]
+    /// Sends a request to the system, identified by the given id if there is one, returning the receiver of its response.
+    fn send(&self, data: Vec<u8>, id: Option<u64>) -> Result<tokio::sync::oneshot::Receiver<Result<Vec<u8>, ChannelError>>, ChannelError> {
+        let (request, response) = match id {
+            Some(id) => Request::with_id(data, self.source.clone(), id),
+            None => Request::new(data),
+        };
+
+        self.outbound.send((self.actor.clone(), self.message_type.clone(), request))
+            .map_err(|_| ChannelError::PeerDisconnected)?;
+
+        Ok(response)
+    }
+
+    /// # [`MemoryChannel::exchange`]
+    /// Sends a request to the system like [`MemoryChannel::send`], and waits for its response.
+    async fn exchange(&self, data: Vec<u8>, id: Option<u64>) -> Result<Vec<u8>, ChannelError> {
+        // The system dropping the request without responding means it shut down
+        self.send(data, id)?.await.unwrap_or(Err(ChannelError::PeerDisconnected))
+    }
+}
+
+impl Channel for MemoryChannel {
+    async fn request(&self, data: Vec<u8>) -> Result<Vec<u8>, ChannelError> {
+        self.exchange(data, None).await
+    }
+
+    async fn send_no_reply(&self, data: Vec<u8>) -> Result<(), ChannelError> {
+        self.send(data, None).map(|_| ())
+    }
+
+    async fn request_with_id(&self, data: Vec<u8>, id: u64) -> Result<Vec<u8>, ChannelError> {
+        self.exchange(data, Some(id)).await
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::message;
+
+    use super::*;
+
+    #[message]
+    struct Ping;
+
+    #[tokio::test]
+    async fn requests_reach_their_system() {
+        let network = MemoryNetwork::new();
+        let a = network.backend("a");
+        let b = network.backend("b");
+
+        let mut systems = network.systems();
+        systems.sort();
+        assert_eq!(systems, ["a", "b"]);
+        assert_eq!(a.systems().await, ["b"]);
+
+        let channel = a.open_channel::<Ping>(ActorID::Numeric(1), "b", "ping").await.unwrap();
+        let response = tokio::spawn(async move { channel.request_with_id(vec![1], 7).await });
+
+        let (actor, message_type, request) = b.incoming().await.unwrap();
+        assert_eq!(actor, ActorID::Numeric(1));
+        assert_eq!(message_type, "ping");
+        assert_eq!(request.data(), [1]);
+        assert_eq!(request.id, Some(("a".to_string(), 7)));
+
+        request.respond(Ok(vec![2])).unwrap();
+        assert_eq!(response.await.unwrap().unwrap(), vec![2]);
+
+        // Requests the system drops without responding fail
+        let channel = a.open_channel::<Ping>(ActorID::Numeric(1), "b", "ping").await.unwrap();
+        let response = tokio::spawn(async move { channel.request(vec![1]).await });
+        drop(b.incoming().await.unwrap());
+        assert!(matches!(response.await.unwrap(), Err(ChannelError::PeerDisconnected)));
+    }
+
+    #[tokio::test]
+    async fn peers_join_and_leave() {
+        let network = MemoryNetwork::new();
+        let a = network.backend("a");
+        let b = network.backend("b");
+
+        assert!(matches!(a.peer_event().await, Some(PeerEvent::Connected { system }) if system == "b"));
+
+        drop(b);
+        assert!(matches!(a.peer_event().await, Some(PeerEvent::Disconnected { system, .. }) if system == "b"));
+        assert!(matches!(
+            a.open_channel::<Ping>(ActorID::Numeric(1), "b", "ping").await,
+            Err(OpenChannelError::SystemUnreachable(system)) if system == "b"
+        ));
+    }
+
+    #[tokio::test]
+    async fn replaced_backends() {
+        let network = MemoryNetwork::new();
+        let a = network.backend("a");
+        let old = network.backend("b");
+        let new = network.backend("b");
+
+        // Dropping the replaced backend leaves the new one on the network
+        drop(old);
+        assert_eq!(a.systems().await, ["b"]);
+
+        let channel = a.open_channel::<Ping>(ActorID::Numeric(1), "b", "ping").await.unwrap();
+        channel.send_no_reply(vec![1]).await.unwrap();
+        assert_eq!(new.incoming().await.unwrap().2.data(), [1]);
+    }
+}
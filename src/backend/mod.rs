@@ -1,12 +1,14 @@
 //! # Backend
 //! [`Backend`]s provide palantir instances connectivity to other instances.
 
+pub mod memory;
 
+use std::time::Duration;
 
-use fluxion::{IndeterminateMessage, Message, MessageSendError};
-use serde::{Deserialize, Serialize};
+use fluxion::Message;
+use thiserror::Error;
 
-use crate::actor_id::ActorID;
+use crate::{actor_id::ActorID, Request};
 
 
 
@@ -19,9 +21,67 @@ pub trait Backend: Send + Sync + 'static {
 
     /// # [`Backend::open_channel`]
     /// Opens a channel with the given message type, to the given actor, on the given system.
-    /// Returns [`None`] if either the system can not be reached, the actor does not exist,
+    /// The message type is given by its id, and `M` may not be the message itself when forwarding requests for other systems.
+    /// 
+    /// # Errors
+    /// Returns an [`OpenChannelError`] if either the system can not be reached, the actor does not exist,
     /// or the actor does not communicate using the given message type.
-    fn open_channel<M: Message>(&self, actor: ActorID, system: &str, message_type: &'static str) -> impl std::future::Future<Output = Option<Self::Channel>> + Send;
+    fn open_channel<M: Message>(&self, actor: ActorID, system: &str, message_type: &str) -> impl std::future::Future<Output = Result<Self::Channel, OpenChannelError>> + Send;
+
+    /// # [`Backend::incoming`]
+    /// Waits for the next inbound request from another system, returning the actor it is addressed to,
+    /// its message type, and the [`Request`] itself. Whatever the [`Request`] is responded with should be sent back to the requester.
+    /// Returns [`None`] once the backend will never receive any more requests.
+    fn incoming(&self) -> impl std::future::Future<Output = Option<(ActorID, String, Request)>> + Send;
+
+    /// # [`Backend::systems`]
+    /// Returns the other systems this backend is currently connected to, which is who [`Palantir::broadcast`](crate::Palantir::broadcast) sends to.
+    /// 
+    /// The default implementation returns no systems, for backends that can't enumerate their peers.
+    fn systems(&self) -> impl std::future::Future<Output = Vec<String>> + Send {
+        async { Vec::new() }
+    }
+
+    /// # [`Backend::peer_event`]
+    /// Waits for the next change in which systems this backend is connected to, which palantir publishes as an [`Event`](crate::Event).
+    /// Returns [`None`] once there will never be any more changes. This is polled alongside [`Backend::incoming`],
+    /// so it has to be cancel safe.
+    /// 
+    /// The default implementation returns [`None`], for backends that don't track their peers.
+    fn peer_event(&self) -> impl std::future::Future<Output = Option<PeerEvent>> + Send {
+        async { None }
+    }
+}
+
+/// # [`PeerEvent`]
+/// A change in which systems a [`Backend`] is connected to.
+#[derive(Clone, Debug)]
+pub enum PeerEvent {
+    /// # [`PeerEvent::Connected`]
+    /// The backend connected to a system, or reconnected to it.
+    Connected {
+        /// The system that was connected to
+        system: String,
+    },
+    /// # [`PeerEvent::Disconnected`]
+    /// The backend lost its connection to a system.
+    Disconnected {
+        /// The system that was disconnected from
+        system: String,
+        /// A description of why the connection was lost
+        reason: String,
+    },
+}
+
+impl PeerEvent {
+    /// # [`PeerEvent::system`]
+    /// Returns the system that was connected to or disconnected from.
+    #[must_use]
+    pub fn system(&self) -> &str {
+        match self {
+            Self::Connected { system } | Self::Disconnected { system, .. } => system,
+        }
+    }
 }
 
 /// # [`Channel`]
@@ -31,8 +91,124 @@ pub trait Channel: Send + Sync + 'static {
 
     /// # [`Channel::request`]
     /// Sends data to the actor, and waits for a response.
-    /// This method should return a [`MessageSendError`] in case of an error in transmission.
-    fn request(&self, data: Vec<u8>) -> impl std::future::Future<Output = Result<Vec<u8>, MessageSendError>> + Send;
+    /// This method should return a [`ChannelError`] describing what went wrong in case of an error in transmission.
+    fn request(&self, data: Vec<u8>) -> impl std::future::Future<Output = Result<Vec<u8>, ChannelError>> + Send;
 
+    /// # [`Channel::send_no_reply`]
+    /// Sends data to the actor without waiting for a response, returning once the data has been sent.
+    /// Backends should flag these on the wire so that the receiving end doesn't transmit a response;
+    /// when delivering one inbound, the backend can simply drop the [`Request`]'s response receiver.
+    /// 
+    /// The default implementation falls back to [`Channel::request`] and discards the response.
+    fn send_no_reply(&self, data: Vec<u8>) -> impl std::future::Future<Output = Result<(), ChannelError>> + Send {
+        async move {
+            self.request(data).await.map(|_| ())
+        }
+    }
+
+    /// # [`Channel::request_with_id`]
+    /// Sends data to the actor like [`Channel::request`], identified by an id that is unique among this system's requests.
+    /// Palantir sends every retry of the same request with the same id, so backends that carry it to the remote end
+    /// should deliver the request with [`Request::with_id`], which lets the remote system deduplicate it.
+    /// 
+    /// The default implementation ignores the id, and falls back to [`Channel::request`].
+    fn request_with_id(&self, data: Vec<u8>, id: u64) -> impl std::future::Future<Output = Result<Vec<u8>, ChannelError>> + Send {
+        let _ = id;
+        self.request(data)
+    }
+
+    /// # [`Channel::is_retryable`]
+    /// Returns whether a request that failed with the given error certainly didn't reach the remote actor,
+    /// so that it is safe to send again (see [`RetryPolicy`](crate::RetryPolicy)).
+    /// 
+    /// The default implementation only considers [`ChannelError::Interrupted`] and [`ChannelError::Overloaded`] retryable.
+    fn is_retryable(&self, error: &ChannelError) -> bool {
+        matches!(error, ChannelError::Interrupted | ChannelError::Overloaded)
+    }
+
+}
+
+/// # [`OpenChannelError`]
+/// The ways in which opening a [`Channel`] can fail.
+/// Callers may want to retry [`OpenChannelError::SystemUnreachable`], but the other variants will not resolve themselves.
+#[derive(Error, Debug)]
+pub enum OpenChannelError {
+    /// # [`OpenChannelError::SystemUnreachable`]
+    /// The system could not be reached.
+    #[error("system {0} could not be reached")]
+    SystemUnreachable(String),
+    /// # [`OpenChannelError::ActorNotFound`]
+    /// The system was reached, but has no such actor.
+    #[error("the actor does not exist on the system")]
+    ActorNotFound,
+    /// # [`OpenChannelError::MessageNotHandled`]
+    /// The actor exists, but does not handle the given message type.
+    #[error("the actor does not handle message type {0}")]
+    MessageNotHandled(String),
+}
 
+/// # [`ChannelError`]
+/// The ways in which a request over a [`Channel`] can fail.
+/// Backends should pick the most specific variant available, as callers may
+/// handle e.g. a timeout differently from a disconnected peer.
+#[derive(Error, Debug, Clone)]
+pub enum ChannelError {
+    /// # [`ChannelError::Serialization`]
+    /// The message or its response could not be serialized or deserialized by the remote end.
+    #[error("the remote end failed to serialize or deserialize the message: {0}")]
+    Serialization(String),
+    /// # [`ChannelError::Closed`]
+    /// The channel was closed, and will not carry any more requests.
+    #[error("the channel is closed")]
+    Closed,
+    /// # [`ChannelError::PeerDisconnected`]
+    /// The system on the other end of the channel disconnected.
+    #[error("the peer disconnected")]
+    PeerDisconnected,
+    /// # [`ChannelError::Timeout`]
+    /// No response was received within the channel's timeout.
+    #[error("the request timed out after {elapsed:?}")]
+    Timeout {
+        /// How long the request waited before timing out
+        elapsed: Duration,
+    },
+    /// # [`ChannelError::HandlerNotFound`]
+    /// The remote system has no handler for the message type registered on the target actor.
+    #[error("the remote system has no handler for this actor and message type")]
+    HandlerNotFound,
+    /// # [`ChannelError::Expired`]
+    /// The request's time to live passed before it could be handled, so it was dropped.
+    #[error("the request expired before it could be handled")]
+    Expired,
+    /// # [`ChannelError::Leaving`]
+    /// The remote system is leaving the cluster, so it no longer accepts requests.
+    #[error("the remote system is leaving the cluster")]
+    Leaving,
+    /// # [`ChannelError::RemoteHandler`]
+    /// The remote actor failed to handle the message.
+    #[error("the remote handler failed to handle the message")]
+    RemoteHandler,
+    /// # [`ChannelError::Overloaded`]
+    /// The remote actor's queue was full, so the request was rejected (see [`OverflowPolicy`](crate::OverflowPolicy)).
+    #[error("the remote handler is overloaded")]
+    Overloaded,
+    /// # [`ChannelError::Interrupted`]
+    /// The request was interrupted before it reached the remote system, e.g. because its stream was reset
+    /// or the connection is migrating, so it is safe to send again.
+    #[error("the request was interrupted before it reached the remote system")]
+    Interrupted,
+    /// # [`ChannelError::TooLarge`]
+    /// The request was larger than the remote system accepts for its message type, so it was dropped without being decoded.
+    #[error("the request is {size} bytes, but the remote system only accepts {limit}")]
+    TooLarge {
+        /// The request's size in bytes
+        size: usize,
+        /// The largest size the remote system accepts in bytes
+        limit: usize,
+    },
+    /// # [`ChannelError::Unroutable`]
+    /// A gateway couldn't forward the request to its target system, because it was forwarded through too many gateways
+    /// or its route leads back to the system it came from.
+    #[error("the request could not be routed to its target system")]
+    Unroutable,
 }
\ No newline at end of file
@@ -0,0 +1,208 @@
+//! # Bounded senders
+//! A [`BoundedSender`] wraps a [`MessageSender`] and limits how many requests can be in flight through it at once.
+//! [`MessageSender::send`] waits for a free slot, while [`BoundedSender::try_send`] fails immediately if there is none,
+//! so latency-sensitive actors can shed load instead of waiting indefinitely.
+
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+use fluxion::{Message, MessageSendError, MessageSender};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+
+
+/// # [`TrySendError`]
+/// The ways in which [`BoundedSender::try_send`] can fail.
+#[derive(Error, Debug)]
+pub enum TrySendError<M> {
+    /// # [`TrySendError::Full`]
+    /// The sender's in-flight window is full, so the message, which is returned, wasn't sent.
+    #[error("the sender's in-flight window is full")]
+    Full(M),
+    /// # [`TrySendError::Send`]
+    /// The message was sent, but sending it failed.
+    #[error(transparent)]
+    Send(MessageSendError),
+}
+
+/// # [`BoundedSender`]
+/// A [`MessageSender`] that allows at most a fixed number of requests in flight at once.
+pub struct BoundedSender<M> {
+    /// The sender requests are sent through
+    inner: Arc<dyn MessageSender<M>>,
+    /// One permit for every free slot in the window
+    slots: Semaphore,
+    /// How many requests can be in flight at once
+    window: usize,
+    /// How many requests are waiting for a free slot
+    waiting: AtomicUsize,
+}
+
+impl<M: Message> BoundedSender<M> {
+    /// # [`BoundedSender::new`]
+    /// Wraps the given sender, allowing at most `window` requests in flight through it at once.
+    /// The window is capped at [`Semaphore::MAX_PERMITS`].
+    #[must_use]
+    pub fn new(inner: Arc<dyn MessageSender<M>>, window: usize) -> Self {
+        let window = window.min(Semaphore::MAX_PERMITS);
+
+        Self {
+            inner,
+            slots: Semaphore::new(window),
+            window,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// # [`BoundedSender::try_send`]
+    /// Sends a message if there is a free slot in the window, and waits for its response.
+    ///
+    /// # Errors
+    /// Returns [`TrySendError::Full`] with the message if the window is full,
+    /// or [`TrySendError::Send`] if sending the message failed.
+    pub async fn try_send(&self, message: M) -> Result<M::Result, TrySendError<M>> {
+        let Ok(_slot) = self.slots.try_acquire() else {
+            return Err(TrySendError::Full(message));
+        };
+
+        self.inner.send(message).await.map_err(TrySendError::Send)
+    }
+
+    /// # [`BoundedSender::capacity`]
+    /// Returns how many more requests can currently be sent without waiting.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.slots.available_permits()
+    }
+
+    /// # [`BoundedSender::pending`]
+    /// Returns how many requests are currently in flight, or waiting for a slot in the window.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.window - self.capacity() + self.waiting.load(Ordering::Relaxed)
+    }
+
+    /// # [`BoundedSender::window`]
+    /// Returns how many requests can be in flight at once.
+    #[must_use]
+    pub fn window(&self) -> usize {
+        self.window
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Message> MessageSender<M> for BoundedSender<M> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        // The semaphore is never closed
+        let waiting = Waiting::new(&self.waiting);
+        let _slot = self.slots.acquire().await
+            .map_err(|e| MessageSendError::UnknownError(Box::new(e)))?;
+        drop(waiting);
+
+        self.inner.send(message).await
+    }
+}
+
+/// # [`Waiting`]
+/// Counts a request as waiting for a slot until it is dropped, including when the request is cancelled while waiting.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    /// # [`Waiting::new`]
+    /// Counts a request as waiting in the given counter.
+    fn new(waiting: &'a AtomicUsize) -> Self {
+        waiting.fetch_add(1, Ordering::Relaxed);
+        Self(waiting)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use fluxion::message;
+
+    use super::*;
+
+    #[message(u64)]
+    struct Ping(u64);
+
+    /// Holds every message until a permit is added to `gate`, then responds with it
+    struct Gated {
+        gate: Semaphore,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageSender<Ping> for Gated {
+        async fn send(&self, message: Ping) -> Result<u64, MessageSendError> {
+            self.gate.acquire().await.unwrap().forget();
+            Ok(message.0)
+        }
+    }
+
+    /// Waits until the sender reports the given number of pending requests
+    async fn wait_pending(sender: &BoundedSender<Ping>, pending: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sender.pending() != pending {
+                tokio::task::yield_now().await;
+            }
+        }).await.expect("requests should become pending");
+    }
+
+    #[tokio::test]
+    async fn window() {
+        let inner = Arc::new(Gated { gate: Semaphore::new(0) });
+        let sender = Arc::new(BoundedSender::new(inner.clone(), 2));
+        assert_eq!(sender.window(), 2);
+        assert_eq!(sender.capacity(), 2);
+
+        // Fill the window
+        let first = tokio::spawn({ let sender = sender.clone(); async move { sender.send(Ping(1)).await.is_ok() } });
+        let second = tokio::spawn({ let sender = sender.clone(); async move { sender.try_send(Ping(2)).await.is_ok() } });
+        wait_pending(&sender, 2).await;
+        assert_eq!(sender.capacity(), 0);
+
+        // The window is full, so the message is handed back
+        let Err(TrySendError::Full(Ping(3))) = sender.try_send(Ping(3)).await else {
+            panic!("expected the window to be full");
+        };
+
+        // Senders waiting for a slot are pending as well
+        let third = tokio::spawn({ let sender = sender.clone(); async move { sender.send(Ping(4)).await.ok() } });
+        wait_pending(&sender, 3).await;
+
+        inner.gate.add_permits(3);
+        assert!(first.await.unwrap());
+        assert!(second.await.unwrap());
+        assert_eq!(third.await.unwrap(), Some(4));
+        assert_eq!(sender.pending(), 0);
+        assert_eq!(sender.capacity(), 2);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiter() {
+        let inner = Arc::new(Gated { gate: Semaphore::new(0) });
+        let sender = Arc::new(BoundedSender::new(inner.clone(), 1));
+
+        let first = tokio::spawn({ let sender = sender.clone(); async move { sender.send(Ping(1)).await.is_ok() } });
+        let waiter = tokio::spawn({ let sender = sender.clone(); async move { sender.send(Ping(2)).await.is_ok() } });
+        wait_pending(&sender, 2).await;
+
+        // A waiter that is cancelled is no longer pending
+        waiter.abort();
+        let _ = waiter.await;
+        assert_eq!(sender.pending(), 1);
+
+        inner.gate.add_permits(1);
+        assert!(first.await.unwrap());
+        assert_eq!(sender.pending(), 0);
+    }
+}
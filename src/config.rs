@@ -0,0 +1,165 @@
+//! # Config
+//! Contains [`Config`], which holds every setting of a palantir instance that can be changed while it is running.
+//! The current settings can be read with [`Palantir::config`](crate::Palantir::config), and changed all at once with
+//! [`Palantir::apply_config`](crate::Palantir::apply_config), without restarting the instance.
+
+use std::{collections::HashMap, time::Duration};
+
+use crate::{idempotency::DEFAULT_IDEMPOTENCY_TTL, OutboxConfig, RetryPolicy};
+
+
+
+/// # [`Config`]
+/// The runtime settings of a palantir instance.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The window in which duplicate inbound requests are responded to with the first delivery's response, or [`None`] if deduplication is disabled.
+    /// See [`Palantir::set_dedup_window`](crate::Palantir::set_dedup_window).
+    pub dedup_window: Option<Duration>,
+    /// How long the responses to requests with idempotency keys are cached for.
+    /// See [`Palantir::set_idempotency_ttl`](crate::Palantir::set_idempotency_ttl).
+    pub idempotency_ttl: Duration,
+    /// How messages to unreachable systems are held, or [`None`] if they aren't.
+    /// See [`Palantir::set_outbox`](crate::Palantir::set_outbox).
+    pub outbox: Option<OutboxConfig>,
+    /// The gateway of every federation zone, keyed by zone.
+    /// See [`Palantir::add_gateway`](crate::Palantir::add_gateway).
+    pub gateways: HashMap<String, String>,
+    /// The backend address of every routed system, keyed by system.
+    /// See [`Palantir::add_route`](crate::Palantir::add_route).
+    pub routes: HashMap<String, String>,
+    /// How long senders wait for a response, or [`None`] if they wait forever.
+    /// See [`Palantir::set_request_timeout`](crate::Palantir::set_request_timeout).
+    pub request_timeout: Option<Duration>,
+    /// The request timeouts of individual message types, keyed by message type, which take precedence over `request_timeout`.
+    /// See [`Palantir::set_message_timeout`](crate::Palantir::set_message_timeout).
+    pub message_timeouts: HashMap<String, Duration>,
+    /// How senders retry failed requests, or [`None`] if they don't.
+    /// See [`Palantir::set_retry_policy`](crate::Palantir::set_retry_policy).
+    pub retry_policy: Option<RetryPolicy>,
+    /// The largest inbound payload accepted in bytes, or [`None`] if payloads are unlimited.
+    /// See [`Palantir::set_max_payload_size`](crate::Palantir::set_max_payload_size).
+    pub max_payload_size: Option<usize>,
+    /// The payload limits of individual message types, keyed by message type, which take precedence over `max_payload_size`.
+    /// See [`Palantir::set_message_max_payload_size`](crate::Palantir::set_message_max_payload_size).
+    pub message_max_payload_sizes: HashMap<String, usize>,
+    /// Whether senders check that the foreign system agrees on the schema of their message type.
+    /// See [`Palantir::set_schema_validation`](crate::Palantir::set_schema_validation).
+    pub schema_validation: bool,
+    /// Whether foreign identifiers naming this system resolve to its actors directly.
+    /// See [`Palantir::set_local_resolution`](crate::Palantir::set_local_resolution).
+    pub local_resolution: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dedup_window: None,
+            idempotency_ttl: DEFAULT_IDEMPOTENCY_TTL,
+            outbox: None,
+            gateways: HashMap::new(),
+            routes: HashMap::new(),
+            request_timeout: None,
+            message_timeouts: HashMap::new(),
+            retry_policy: None,
+            max_payload_size: None,
+            message_max_payload_sizes: HashMap::new(),
+            schema_validation: false,
+            local_resolution: false,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, message, ActorContext, Delegate, Handler, Identifier, MessageID};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{testkit::two_systems, PalantirSendError};
+    use super::*;
+
+    #[actor]
+    struct Sleeper;
+
+    /// Sleeps for the given number of milliseconds, and returns them
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Sleep(u64);
+
+    impl Handler<Sleep> for Sleeper {
+        async fn handle_message<D: Delegate>(&self, message: Sleep, _context: &ActorContext<D>) -> u64 {
+            tokio::time::sleep(Duration::from_millis(message.0)).await;
+            message.0
+        }
+    }
+
+    fn config() -> Config {
+        Config {
+            dedup_window: Some(Duration::from_secs(30)),
+            idempotency_ttl: Duration::from_secs(10),
+            gateways: HashMap::from([("zone".to_string(), "gateway".to_string())]),
+            routes: HashMap::from([("c".to_string(), "c.example:4433".to_string())]),
+            request_timeout: Some(Duration::from_secs(5)),
+            message_timeouts: HashMap::from([(Sleep::ID.to_string(), Duration::from_millis(50))]),
+            max_payload_size: Some(1024),
+            message_max_payload_sizes: HashMap::from([(Sleep::ID.to_string(), 64)]),
+            schema_validation: true,
+            local_resolution: true,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let (a, _b, _guard) = two_systems("a", "b");
+        a.get_delegate().apply_config(config()).unwrap();
+
+        let applied = a.get_delegate().config();
+        let expected = config();
+        assert_eq!(applied.dedup_window, expected.dedup_window);
+        assert_eq!(applied.idempotency_ttl, expected.idempotency_ttl);
+        assert_eq!(applied.gateways, expected.gateways);
+        assert_eq!(applied.routes, expected.routes);
+        assert_eq!(applied.request_timeout, expected.request_timeout);
+        assert_eq!(applied.message_timeouts, expected.message_timeouts);
+        assert_eq!(applied.max_payload_size, expected.max_payload_size);
+        assert_eq!(applied.message_max_payload_sizes, expected.message_max_payload_sizes);
+        assert_eq!(applied.schema_validation, expected.schema_validation);
+        assert_eq!(applied.local_resolution, expected.local_resolution);
+    }
+
+    #[tokio::test]
+    async fn invalid_config_is_not_applied() {
+        let (a, _b, _guard) = two_systems("a", "b");
+
+        let mut invalid = config();
+        invalid.routes.insert("not a system".to_string(), "address".to_string());
+        assert!(a.get_delegate().apply_config(invalid).is_err());
+
+        let current = a.get_delegate().config();
+        assert!(current.routes.is_empty());
+        assert_eq!(current.request_timeout, None);
+        assert!(!current.schema_validation);
+    }
+
+    #[tokio::test]
+    async fn applies_to_new_senders() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Sleeper).await.unwrap();
+        b.get_delegate().register::<Sleeper, Sleep, _>(b.get_local::<Sleeper>(id).await.unwrap()).await;
+
+        let before = a.get::<Sleeper, Sleep>(Identifier::Foreign(id, "b")).await.unwrap();
+        a.get_delegate().apply_config(Config {
+            message_timeouts: HashMap::from([(Sleep::ID.to_string(), Duration::from_millis(50))]),
+            ..Config::default()
+        }).unwrap();
+
+        // Senders opened afterwards use the new timeout, while ones opened before keep the old one
+        let after = a.get::<Sleeper, Sleep>(Identifier::Foreign(id, "b")).await.unwrap();
+        let error = after.send(Sleep(500)).await.unwrap_err();
+        assert!(matches!(PalantirSendError::of(&error), Some(PalantirSendError::Timeout(_))));
+        assert_eq!(before.send(Sleep(100)).await.unwrap(), 100);
+    }
+}
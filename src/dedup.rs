@@ -0,0 +1,140 @@
+//! # Dedup
+//! Provides [`DedupCache`], which remembers the responses to recently received requests by their ids so that redelivered requests
+//! aren't handled twice, and [`next_id`], which generates the ids that outgoing requests are sent with.
+
+use std::{collections::hash_map::RandomState, hash::{BuildHasher, Hasher}, sync::{atomic::{AtomicU64, Ordering}, OnceLock}};
+
+use crate::idempotency::ResponseCache;
+
+
+
+/// # [`RequestId`]
+/// Identifies a request by the system it came from, and the id it was sent with.
+pub(crate) type RequestId = (String, u64);
+
+/// # [`DedupCache`]
+/// Remembers the responses to requests received within a time window, keyed by their ids, and replays them to any redeliveries.
+/// A redelivery that arrives while the request is still being handled waits for its response.
+pub(crate) type DedupCache = ResponseCache<RequestId>;
+
+/// # [`next_id`]
+/// Returns a new id for an outgoing request, which is unique among this process's requests.
+/// Ids start at a random point, so that a restarted system's requests aren't mistaken for ones it sent before.
+pub(crate) fn next_id() -> u64 {
+    static NEXT_ID: OnceLock<AtomicU64> = OnceLock::new();
+
+    NEXT_ID.get_or_init(|| AtomicU64::new(RandomState::new().build_hasher().finish()))
+        .fetch_add(1, Ordering::Relaxed)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use fluxion::{actor, message, ActorContext, Delegate, Fluxion, Handler, MessageID};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{backend::ChannelError, serializer::Pot, testkit::{decode, encode, MockBackend}, ActorID, Palantir, Request};
+    use super::*;
+
+    fn id(system: &str, id: u64) -> RequestId {
+        (system.to_string(), id)
+    }
+
+    #[test]
+    fn redeliveries_get_the_first_response() {
+        let mut cache = DedupCache::new(Duration::from_secs(60));
+
+        let (first, _) = Request::new(vec![1]);
+        assert!(cache.begin(id("a", 1), first).is_some());
+        cache.finish(id("a", 1), &Ok(vec![2]));
+
+        let (again, mut response) = Request::new(vec![1]);
+        assert!(cache.begin(id("a", 1), again).is_none());
+        assert_eq!(response.try_recv().unwrap().unwrap(), vec![2]);
+
+        // Ids are scoped to the system that sent them
+        let (other, _) = Request::new(vec![1]);
+        assert!(cache.begin(id("b", 1), other).is_some());
+        let (other, _) = Request::new(vec![1]);
+        assert!(cache.begin(id("a", 2), other).is_some());
+    }
+
+    #[test]
+    fn failed_requests_are_handled_again() {
+        let mut cache = DedupCache::new(Duration::from_secs(60));
+
+        let (first, _) = Request::new(vec![1]);
+        assert!(cache.begin(id("a", 1), first).is_some());
+        cache.finish(id("a", 1), &Err(ChannelError::Overloaded));
+
+        let (retry, _) = Request::new(vec![1]);
+        assert!(cache.begin(id("a", 1), retry).is_some());
+    }
+
+    #[test]
+    fn ids_expire() {
+        let mut cache = DedupCache::new(Duration::ZERO);
+
+        let (first, _) = Request::new(vec![1]);
+        assert!(cache.begin(id("a", 1), first).is_some());
+        cache.finish(id("a", 1), &Ok(vec![2]));
+
+        let (again, _) = Request::new(vec![1]);
+        assert!(cache.begin(id("a", 1), again).is_some());
+        cache.finish(id("a", 1), &Ok(vec![2]));
+
+        // Widening the window keeps the responses already cached
+        cache.set_ttl(Duration::from_secs(60));
+        let (again, _) = Request::new(vec![1]);
+        assert!(cache.begin(id("a", 1), again).is_none());
+    }
+
+    #[test]
+    fn next_ids_are_unique() {
+        let first = next_id();
+        assert_ne!(first, next_id());
+    }
+
+    /// Counts the messages it handles
+    #[actor]
+    struct Counter(std::sync::atomic::AtomicU32);
+
+    #[message(u32)]
+    #[derive(Serialize, Deserialize)]
+    struct Count;
+
+    impl Handler<Count> for Counter {
+        async fn handle_message<D: Delegate>(&self, _message: Count, _context: &ActorContext<D>) -> u32 {
+            self.0.fetch_add(1, Ordering::Relaxed) + 1
+        }
+    }
+
+    #[tokio::test]
+    async fn redeliveries_are_replayed() {
+        let system = Fluxion::new("a", Palantir::new("a".to_string(), MockBackend::new(|_, _, _, _| Err(ChannelError::Closed))).unwrap());
+        let id = system.add(Counter(0.into())).await.unwrap();
+        system.get_delegate().register::<Counter, Count, _>(system.get_local::<Counter>(id).await.unwrap()).await;
+        system.get_delegate().set_dedup_window(Some(Duration::from_secs(60)));
+
+        let serving = system.clone();
+        let _serving = tokio::spawn(async move { serving.get_delegate().serve().await });
+
+        let send = |request_id| {
+            let (request, response) = Request::with_id(encode::<Pot>(&Count), "b".to_string(), request_id);
+            system.get_delegate().backend.inject_request(ActorID::Numeric(id), Count::ID, request);
+            response
+        };
+
+        // A retry of a request that was already handled gets its response, instead of being handled again
+        assert_eq!(decode::<Pot, u32>(&send(1).await.unwrap().unwrap()), 1);
+        assert_eq!(decode::<Pot, u32>(&send(1).await.unwrap().unwrap()), 1);
+        assert_eq!(decode::<Pot, u32>(&send(2).await.unwrap().unwrap()), 2);
+
+        // Without deduplication every delivery is handled
+        system.get_delegate().set_dedup_window(None);
+        assert_eq!(decode::<Pot, u32>(&send(1).await.unwrap().unwrap()), 3);
+    }
+}
@@ -0,0 +1,137 @@
+//! # Error
+//! Contains [`PalantirSendError`], which describes why palantir failed to send a message.
+//! Fluxion's senders can only fail with a [`MessageSendError`], so every [`PalantirSendError`] is converted into one,
+//! and can be retrieved from it again with [`PalantirSendError::of`].
+
+use std::error::Error as StdError;
+
+use fluxion::MessageSendError;
+use thiserror::Error;
+
+use crate::{backend::{ChannelError, OpenChannelError}, SendTimeout};
+
+
+
+/// # [`PalantirSendError`]
+/// The ways in which sending a message to a foreign actor can fail.
+#[derive(Error, Debug)]
+pub enum PalantirSendError {
+    /// # [`PalantirSendError::Serialization`]
+    /// The message couldn't be serialized.
+    #[error("failed to serialize message {message_type}")]
+    Serialization {
+        /// The message's type
+        message_type: &'static str,
+        /// Why the message couldn't be serialized
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    /// # [`PalantirSendError::Deserialization`]
+    /// The response couldn't be deserialized.
+    #[error("failed to deserialize response to {message_type}")]
+    Deserialization {
+        /// The type of the message that was responded to
+        message_type: &'static str,
+        /// Why the response couldn't be deserialized
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    /// # [`PalantirSendError::Journal`]
+    /// The message couldn't be recorded in the journal, so it wasn't sent.
+    #[error("failed to journal message {message_type}")]
+    Journal {
+        /// The message's type
+        message_type: &'static str,
+        /// Why the message couldn't be recorded
+        source: std::io::Error,
+    },
+    /// # [`PalantirSendError::ActorNotFound`]
+    /// The system was reached, but has no such actor, or the actor doesn't handle the message type.
+    #[error("the actor does not exist on the system, or does not handle the message type")]
+    ActorNotFound,
+    /// # [`PalantirSendError::Open`]
+    /// A channel to the actor couldn't be opened for any other reason.
+    #[error(transparent)]
+    Open(OpenChannelError),
+    /// # [`PalantirSendError::Transport`]
+    /// The channel failed to carry the request or its response.
+    #[error(transparent)]
+    Transport(ChannelError),
+    /// # [`PalantirSendError::RemoteHandler`]
+    /// The actor failed to handle the message.
+    #[error("the remote handler failed to handle the message")]
+    RemoteHandler,
+    /// # [`PalantirSendError::Timeout`]
+    /// The request wasn't responded to within its timeout.
+    #[error(transparent)]
+    Timeout(#[from] SendTimeout),
+    /// # [`PalantirSendError::SchemaMismatch`]
+    /// The actor's registration has a different schema for the message type than the sender (see [`schema`](crate::schema)),
+    /// so the message wasn't sent.
+    #[error("the actor's schema for message type {message_type} has fingerprint {remote:#x}, but the sender's has {local:#x}")]
+    SchemaMismatch {
+        /// The message type
+        message_type: &'static str,
+        /// The sender's fingerprint of the message type
+        local: u64,
+        /// The registration's fingerprint of the message type
+        remote: u64,
+    },
+}
+
+impl PalantirSendError {
+    /// # [`PalantirSendError::serialization`]
+    /// Creates a [`PalantirSendError::Serialization`] for the given message type.
+    pub(crate) fn serialization(message_type: &'static str, source: impl StdError + Send + Sync + 'static) -> Self {
+        Self::Serialization { message_type, source: Box::new(source) }
+    }
+
+    /// # [`PalantirSendError::deserialization`]
+    /// Creates a [`PalantirSendError::Deserialization`] for the given message type.
+    pub(crate) fn deserialization(message_type: &'static str, source: impl StdError + Send + Sync + 'static) -> Self {
+        Self::Deserialization { message_type, source: Box::new(source) }
+    }
+
+    /// # [`PalantirSendError::of`]
+    /// Returns the [`PalantirSendError`] that caused the given [`MessageSendError`],
+    /// or [`None`] if it wasn't returned by palantir.
+    #[must_use]
+    pub fn of(error: &MessageSendError) -> Option<&Self> {
+        match error {
+            MessageSendError::SerializationError { source, .. }
+            | MessageSendError::DeserializationError { source, .. }
+            | MessageSendError::DelegateError { source, .. }
+            | MessageSendError::UnknownError(source) => source.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl From<OpenChannelError> for PalantirSendError {
+    fn from(error: OpenChannelError) -> Self {
+        match error {
+            OpenChannelError::ActorNotFound | OpenChannelError::MessageNotHandled(_) => Self::ActorNotFound,
+            error @ OpenChannelError::SystemUnreachable(_) => Self::Open(error),
+        }
+    }
+}
+
+impl From<ChannelError> for PalantirSendError {
+    fn from(error: ChannelError) -> Self {
+        match error {
+            ChannelError::HandlerNotFound => Self::ActorNotFound,
+            ChannelError::RemoteHandler => Self::RemoteHandler,
+            error => Self::Transport(error),
+        }
+    }
+}
+
+impl From<PalantirSendError> for MessageSendError {
+    fn from(error: PalantirSendError) -> Self {
+        let message = error.to_string();
+
+        match error {
+            PalantirSendError::Serialization { .. } => Self::SerializationError { message, source: Box::new(error) },
+            PalantirSendError::Deserialization { .. } => Self::DeserializationError { message, source: Box::new(error) },
+            _ => Self::DelegateError { message, source: Box::new(error) },
+        }
+    }
+}
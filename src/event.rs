@@ -0,0 +1,91 @@
+//! # Event
+//! Contains the [`Event`] enum, which is published by [`Palantir`](crate::Palantir) on a broadcast channel
+//! so that applications and metric exporters can observe it from a single subscription point.
+
+use crate::ActorID;
+
+
+
+/// # [`Event`]
+/// Something notable that happened inside of a palantir instance.
+/// These are received through [`Palantir::events`](crate::Palantir::events).
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// # [`Event::RegistrationAdded`]
+    /// A local actor was registered as handling a message type.
+    RegistrationAdded {
+        /// The local actor's id
+        actor: u64,
+        /// The message type the actor was registered for
+        message_type: &'static str,
+    },
+    /// # [`Event::RegistrationRemoved`]
+    /// A local actor's registration for a message type was removed.
+    RegistrationRemoved {
+        /// The local actor's id
+        actor: u64,
+        /// The message type the actor was registered for
+        message_type: &'static str,
+    },
+    /// # [`Event::ChannelOpened`]
+    /// A channel was opened to an actor on a foreign system.
+    ChannelOpened {
+        /// The foreign system the channel was opened to
+        system: String,
+        /// The actor the channel was opened to
+        actor: ActorID,
+        /// The message type carried by the channel
+        message_type: &'static str,
+    },
+    /// # [`Event::RequestFailed`]
+    /// A request to an actor on a foreign system failed.
+    RequestFailed {
+        /// The foreign system the request was sent to
+        system: String,
+        /// The actor the request was sent to
+        actor: ActorID,
+        /// The message type of the request
+        message_type: &'static str,
+        /// A description of why the request failed
+        reason: String,
+    },
+    /// # [`Event::SystemLeaving`]
+    /// A foreign system rejected a request because it is leaving the cluster.
+    SystemLeaving {
+        /// The system that is leaving
+        system: String,
+    },
+    /// # [`Event::HandlerPanicked`]
+    /// A local actor panicked while handling a message.
+    HandlerPanicked {
+        /// The local actor's id
+        actor: u64,
+        /// The message type being handled
+        message_type: &'static str,
+        /// How many times the actor has panicked while handling this message type
+        panics: usize,
+    },
+    /// # [`Event::RelayRestarted`]
+    /// The task relaying requests to a local actor panicked, and was restarted.
+    RelayRestarted {
+        /// The local actor's id
+        actor: u64,
+        /// How many times the actor's relay task has been restarted
+        restarts: usize,
+    },
+    /// # [`Event::PeerConnected`]
+    /// The backend connected to a foreign system, or reconnected to it. Senders to the system that failed while it was disconnected
+    /// reopen their channels on their next send, so this is a good time to retry anything that failed.
+    PeerConnected {
+        /// The foreign system
+        system: String,
+    },
+    /// # [`Event::PeerDisconnected`]
+    /// The backend lost its connection to a foreign system.
+    PeerDisconnected {
+        /// The foreign system
+        system: String,
+        /// A description of why the connection was lost
+        reason: String,
+    },
+}
@@ -0,0 +1,81 @@
+//! # Fanout
+//! [`Palantir::broadcast`](crate::Palantir::broadcast) sends a message to every system its backend is connected to,
+//! without knowing which actor handles it on each. The message is addressed to a reserved actor, and each receiving
+//! palantir instance delivers it to the actor registered for its message type with the lowest id. Systems without
+//! such a registration respond with [`ChannelError::HandlerNotFound`](crate::backend::ChannelError::HandlerNotFound).
+
+use fluxion::{Message, MessageID};
+use serde::{Deserialize, Serialize};
+
+
+
+/// # [`BROADCAST_ACTOR`]
+/// The actor broadcasts are addressed to.
+pub(crate) const BROADCAST_ACTOR: &str = "palantir::broadcast";
+
+/// # [`Broadcast`]
+/// A broadcast message, which should be delivered to an actor handling the given message type.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Broadcast {
+    /// The message's actual type
+    pub message_type: String,
+    /// The serialized message
+    pub data: Vec<u8>,
+}
+
+impl Message for Broadcast {
+    /// The response, still serialized
+    type Result = Vec<u8>;
+}
+
+impl MessageID for Broadcast {
+    const ID: &'static str = "palantir::fanout::Broadcast";
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, message, ActorContext, Delegate, Handler};
+
+    use crate::testkit::two_systems;
+    use super::*;
+
+    #[actor]
+    struct Named(&'static str);
+
+    #[message(String)]
+    #[derive(Serialize, Deserialize)]
+    struct Name;
+
+    impl Handler<Name> for Named {
+        async fn handle_message<D: Delegate>(&self, _message: Name, _context: &ActorContext<D>) -> String {
+            self.0.to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn broadcast() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        // Systems without a registration for the message type are left out
+        assert!(a.get_delegate().broadcast(Name).await.unwrap().is_empty());
+
+        // The actor with the lowest id receives it, even if it was registered last
+        let lowest = b.add(Named("lowest")).await.unwrap();
+        let highest = b.add(Named("highest")).await.unwrap();
+        assert!(lowest < highest);
+        for id in [highest, lowest] {
+            b.get_delegate().register::<Named, Name, _>(b.get_local::<Named>(id).await.unwrap()).await;
+        }
+
+        let responses = a.get_delegate().broadcast(Name).await.unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses["b"].as_ref().unwrap(), "lowest");
+
+        // Once it is removed, the next lowest receives it
+        b.get_delegate().unregister::<Named, Name>(lowest).await;
+        let responses = a.get_delegate().broadcast(Name).await.unwrap();
+        assert_eq!(responses["b"].as_ref().unwrap(), "highest");
+    }
+}
@@ -0,0 +1,405 @@
+//! # Federation
+//! System ids may be hierarchical, with levels separated by `.` (e.g. `region.cluster.node`).
+//! Any prefix of these levels (e.g. `region` or `region.cluster`) is a federation zone, and systems in a zone
+//! that this system can't reach directly can be reached by forwarding requests through one of the zone's gateway systems.
+//! This module contains the routing rules ([`Gateways`]) and the wire types used to forward requests through gateways.
+//!
+//! The routing rules also map system ids to the addresses their backend knows them by (e.g. socket addresses or peer ids),
+//! so that application code only ever uses logical system ids. Systems without a route are passed to the backend as is.
+
+use std::{collections::HashMap, sync::RwLock};
+
+use fluxion::{Message, MessageID};
+use serde::{Deserialize, Serialize};
+
+use crate::{backend::{Backend, Channel, ChannelError, OpenChannelError}, ActorID};
+
+
+
+/// # [`GATEWAY_ACTOR`]
+/// The reserved actor name that [`Forward`] requests are addressed to.
+pub(crate) const GATEWAY_ACTOR: &str = "palantir::gateway";
+
+/// # [`MAX_HOPS`]
+/// How many gateways a request may be forwarded through, so that misconfigured gateways can't forward it forever.
+pub(crate) const MAX_HOPS: u8 = 8;
+
+/// # [`Gateways`]
+/// Maps federation zones to the gateway systems that requests to them should be forwarded through,
+/// and systems to their backend addresses.
+pub(crate) struct Gateways {
+    /// This system's id. Requests are never forwarded to ourselves.
+    system_id: String,
+    /// Maps zones to gateways
+    rules: HashMap<String, String>,
+    /// Maps systems to backend addresses
+    routes: HashMap<String, String>,
+}
+
+impl Gateways {
+    /// # [`Gateways::new`]
+    /// Creates an empty set of routing rules for the given system.
+    pub fn new(system_id: String) -> Self {
+        Self {
+            system_id,
+            rules: HashMap::new(),
+            routes: HashMap::new(),
+        }
+    }
+
+    /// # [`Gateways::insert`]
+    /// Routes the given zone through the given gateway, returning the zone's previous gateway.
+    pub fn insert(&mut self, zone: String, gateway: String) -> Option<String> {
+        self.rules.insert(zone, gateway)
+    }
+
+    /// # [`Gateways::remove`]
+    /// Removes the given zone's gateway, returning it.
+    pub fn remove(&mut self, zone: &str) -> Option<String> {
+        self.rules.remove(zone)
+    }
+
+    /// # [`Gateways::rules`]
+    /// Returns the gateway of every zone, keyed by zone.
+    pub fn rules(&self) -> &HashMap<String, String> {
+        &self.rules
+    }
+
+    /// # [`Gateways::replace`]
+    /// Replaces every zone's gateway with the given ones.
+    pub fn replace(&mut self, rules: HashMap<String, String>) {
+        self.rules = rules;
+    }
+
+    /// # [`Gateways::insert_route`]
+    /// Routes the given system to the given backend address, returning the system's previous address.
+    pub fn insert_route(&mut self, system: String, address: String) -> Option<String> {
+        self.routes.insert(system, address)
+    }
+
+    /// # [`Gateways::remove_route`]
+    /// Removes the given system's backend address, returning it.
+    pub fn remove_route(&mut self, system: &str) -> Option<String> {
+        self.routes.remove(system)
+    }
+
+    /// # [`Gateways::routes`]
+    /// Returns the backend address of every routed system, keyed by system.
+    pub fn routes(&self) -> &HashMap<String, String> {
+        &self.routes
+    }
+
+    /// # [`Gateways::replace_routes`]
+    /// Replaces every system's backend address with the given ones.
+    pub fn replace_routes(&mut self, routes: HashMap<String, String>) {
+        self.routes = routes;
+    }
+
+    /// # [`Gateways::address`]
+    /// Returns the backend address of the given system, which is the system id itself if it has no route.
+    pub fn address(&self, system: &str) -> String {
+        self.routes.get(system).map_or_else(|| system.to_string(), Clone::clone)
+    }
+
+    /// # [`Gateways::route`]
+    /// Returns the gateway of the most specific zone containing the given system,
+    /// or [`None`] if the system should be contacted directly.
+    pub fn route(&self, system: &str) -> Option<&str> {
+
+        // Walk up the system's zones, starting with the most specific
+        let mut zone = system;
+        let gateway = loop {
+            if let Some(gateway) = self.rules.get(zone) {
+                break gateway;
+            }
+
+            zone = zone.rsplit_once('.')?.0;
+        };
+
+        // Gateways, including ourselves if we are one, are contacted directly
+        if gateway == system || *gateway == self.system_id {
+            None
+        } else {
+            Some(gateway)
+        }
+    }
+}
+
+/// # [`Forward`]
+/// A request that a gateway should forward to an actor on another system.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Forward {
+    /// The system to forward the request to
+    pub system: String,
+    /// The actor to forward the request to
+    pub actor: ActorID,
+    /// The request's message type
+    pub message_type: String,
+    /// Whether the requester is waiting for a response
+    pub reply: bool,
+    /// How many more gateways, including the one receiving it, may forward the request
+    pub hops: u8,
+    /// The system that sent the request to this gateway, which it is never forwarded back to
+    pub from: String,
+    /// The serialized message
+    pub data: Vec<u8>,
+}
+
+impl Message for Forward {
+    /// The forwarded response, still serialized
+    type Result = Vec<u8>;
+}
+
+impl MessageID for Forward {
+    const ID: &'static str = "palantir::federation::Forward";
+}
+
+/// # [`Link`]
+/// A channel to an actor, either directly or through a gateway.
+pub(crate) enum Link<C> {
+    /// The channel is connected to the actor itself.
+    Direct(C),
+    /// The channel is connected to a gateway, which forwards requests to the actor.
+    Gateway {
+        /// The channel to the gateway
+        channel: C,
+        /// The system the actor is on
+        system: String,
+        /// The actor requests are forwarded to
+        actor: ActorID,
+        /// The message type of the forwarded requests
+        message_type: String,
+        /// How many more gateways, including the one this link is connected to, may forward the requests
+        hops: u8,
+        /// This system's id, which the gateway never forwards the requests back to
+        from: String,
+    },
+}
+
+impl<C: Channel> Link<C> {
+    /// # [`Link::wrap`]
+    /// Wraps the given data in a serialized [`Forward`], if this link is through a gateway.
+    fn wrap(&self, data: Vec<u8>, reply: bool) -> Result<Vec<u8>, ChannelError> {
+        match self {
+            Self::Direct(_) => Ok(data),
+            Self::Gateway { system, actor, message_type, hops, from, .. } => pot::to_vec(&Forward {
+                system: system.clone(),
+                actor: actor.clone(),
+                message_type: message_type.clone(),
+                reply,
+                hops: *hops,
+                from: from.clone(),
+                data,
+            }).map_err(|e| ChannelError::Serialization(e.to_string())),
+        }
+    }
+
+    /// # [`Link::channel`]
+    /// Returns the channel this link sends over.
+    fn channel(&self) -> &C {
+        match self {
+            Self::Direct(channel) | Self::Gateway { channel, .. } => channel,
+        }
+    }
+
+    /// # [`Link::is_retryable`]
+    /// Returns whether a request that failed with the given error is safe to send again. See [`Channel::is_retryable`].
+    pub fn is_retryable(&self, error: &ChannelError) -> bool {
+        self.channel().is_retryable(error)
+    }
+
+    /// # [`Link::request`]
+    /// Sends data to the actor, and waits for a response. See [`Channel::request`].
+    pub async fn request(&self, data: Vec<u8>) -> Result<Vec<u8>, ChannelError> {
+        let data = self.wrap(data, true)?;
+        self.channel().request(data).await
+    }
+
+    /// # [`Link::send_no_reply`]
+    /// Sends data to the actor without waiting for a response. See [`Channel::send_no_reply`].
+    pub async fn send_no_reply(&self, data: Vec<u8>) -> Result<(), ChannelError> {
+        let data = self.wrap(data, false)?;
+        self.channel().send_no_reply(data).await
+    }
+
+    /// # [`Link::request_with_id`]
+    /// Sends data to the actor identified by the given id, and waits for a response. See [`Channel::request_with_id`].
+    /// Through a gateway, the id identifies the request to the gateway rather than the actor's system.
+    pub async fn request_with_id(&self, data: Vec<u8>, id: u64) -> Result<Vec<u8>, ChannelError> {
+        let data = self.wrap(data, true)?;
+        self.channel().request_with_id(data, id).await
+    }
+}
+
+/// # [`open_link`]
+/// Opens a [`Link`] to the given actor on the given system, routing it through a gateway if the system's zone has one.
+/// Links through a gateway are opened without contacting the target system, so any issues reaching it surface on the first request.
+/// Whichever system is contacted directly is passed to the backend by its address.
+///
+/// # Panics
+/// Panics if the gateways lock is poisoned, which should never happen.
+pub(crate) async fn open_link<B: Backend, M: Message>(backend: &B, gateways: &RwLock<Gateways>, actor: ActorID, system: &str, message_type: &str) -> Result<Link<B::Channel>, OpenChannelError> {
+    open_hop::<B, M>(backend, gateways, actor, system, message_type, MAX_HOPS).await
+}
+
+/// # [`open_hop`]
+/// Opens a [`Link`] like [`open_link`], allowing requests through a gateway to be forwarded by the given number of further gateways.
+///
+/// # Panics
+/// Panics if the gateways lock is poisoned, which should never happen.
+async fn open_hop<B: Backend, M: Message>(backend: &B, gateways: &RwLock<Gateways>, actor: ActorID, system: &str, message_type: &str, hops: u8) -> Result<Link<B::Channel>, OpenChannelError> {
+
+    // The gateway forwards to the system by its id, and resolves its address itself
+    let (gateway, address, from) = {
+        let gateways = gateways.read().expect("gateways lock should never be poisoned");
+        let gateway = gateways.route(system);
+        (gateway.is_some(), gateways.address(gateway.unwrap_or(system)), gateways.system_id.clone())
+    };
+
+    if !gateway {
+        return Ok(Link::Direct(backend.open_channel::<M>(actor, &address, message_type).await?));
+    }
+
+    let channel = backend.open_channel::<Forward>(ActorID::Named(GATEWAY_ACTOR.to_string()), &address, Forward::ID).await?;
+
+    Ok(Link::Gateway {
+        channel,
+        system: system.to_string(),
+        actor,
+        message_type: message_type.to_string(),
+        hops,
+        from,
+    })
+}
+
+/// # [`relay`]
+/// Handles a [`Forward`] request on a gateway, by forwarding it to its target and responding with the target's response.
+/// Requests that have run out of hops, or that would be forwarded back to the system they came from,
+/// are responded to with [`ChannelError::Unroutable`].
+///
+/// # Panics
+/// Panics if the gateways lock is poisoned, which should never happen.
+pub(crate) async fn relay<B: Backend>(backend: &B, gateways: &RwLock<Gateways>, request: crate::Request) {
+
+    let forward = match pot::from_slice::<Forward>(request.data()) {
+        Ok(forward) => forward,
+        Err(e) => {
+            let _ = request.respond(Err(ChannelError::Serialization(e.to_string())));
+            return;
+        }
+    };
+
+    // Gateways that route to each other would otherwise forward the request back and forth forever
+    let looped = gateways.read().expect("gateways lock should never be poisoned").route(&forward.system) == Some(forward.from.as_str());
+    let Some(hops) = forward.hops.checked_sub(1).filter(|_| !looped) else {
+        let _ = request.respond(Err(ChannelError::Unroutable));
+        return;
+    };
+
+    // The actual message type is only known by its id here, so open the channel as a forward.
+    // The target may itself be behind another gateway, in which case this just forwards it again.
+    let link = match open_hop::<B, Forward>(backend, gateways, forward.actor, &forward.system, &forward.message_type, hops).await {
+        Ok(link) => link,
+        Err(e) => {
+            let _ = request.respond(Err(match e {
+                OpenChannelError::SystemUnreachable(_) => ChannelError::PeerDisconnected,
+                OpenChannelError::ActorNotFound | OpenChannelError::MessageNotHandled(_) => ChannelError::HandlerNotFound,
+            }));
+            return;
+        }
+    };
+
+    let response = if forward.reply {
+        link.request(forward.data).await
+    } else {
+        link.send_no_reply(forward.data).await.map(|()| Vec::new())
+    };
+
+    let _ = request.respond(response);
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, message, ActorContext, Delegate, Handler, Identifier};
+    use serde::{Deserialize, Serialize};
+
+    use crate::testkit::two_systems;
+    use super::*;
+
+    #[actor]
+    struct Greeter;
+
+    #[message(String)]
+    #[derive(Serialize, Deserialize)]
+    struct Greet;
+
+    impl Handler<Greet> for Greeter {
+        async fn handle_message<D: Delegate>(&self, _message: Greet, _context: &ActorContext<D>) -> String {
+            "hello".to_string()
+        }
+    }
+
+    fn gateways() -> Gateways {
+        let mut gateways = Gateways::new("home.a".to_string());
+        gateways.insert("eu".to_string(), "eu.gateway".to_string());
+        gateways.insert("eu.west".to_string(), "eu.west.gateway".to_string());
+        gateways.insert("home".to_string(), "home.a".to_string());
+        gateways
+    }
+
+    #[test]
+    fn most_specific_zone_wins() {
+        let gateways = gateways();
+
+        assert_eq!(gateways.route("eu.west.node"), Some("eu.west.gateway"));
+        assert_eq!(gateways.route("eu.east.node"), Some("eu.gateway"));
+        assert_eq!(gateways.route("eu"), Some("eu.gateway"));
+    }
+
+    #[test]
+    fn direct_systems() {
+        let gateways = gateways();
+
+        // Systems outside every zone
+        assert_eq!(gateways.route("us.node"), None);
+        assert_eq!(gateways.route("europe"), None);
+
+        // Gateways themselves
+        assert_eq!(gateways.route("eu.gateway"), None);
+        assert_eq!(gateways.route("eu.west.gateway"), None);
+
+        // Zones we are the gateway of
+        assert_eq!(gateways.route("home.b"), None);
+    }
+
+    #[test]
+    fn addresses() {
+        let mut gateways = gateways();
+        gateways.insert_route("eu.gateway".to_string(), "10.0.0.1:4433".to_string());
+
+        assert_eq!(gateways.address("eu.gateway"), "10.0.0.1:4433");
+        assert_eq!(gateways.address("eu.west.gateway"), "eu.west.gateway");
+    }
+
+    #[tokio::test]
+    async fn routed_systems() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Greeter).await.unwrap();
+        b.get_delegate().register::<Greeter, Greet, _>(b.get_local::<Greeter>(id).await.unwrap()).await;
+
+        // There is no system called greeter, but requests to it are routed to b's address
+        assert_eq!(a.get_delegate().add_route("greeter".to_string(), "b".to_string()).unwrap(), None);
+        assert_eq!(a.get_delegate().routes()["greeter"], "b");
+        let sender = a.get::<Greeter, Greet>(Identifier::Foreign(id, "greeter")).await.unwrap();
+        assert_eq!(sender.send(Greet).await.unwrap(), "hello");
+
+        // Senders opened after the route is removed can't reach it
+        assert_eq!(a.get_delegate().remove_route("greeter").as_deref(), Some("b"));
+        let sender = a.get_delegate().open_sender::<Greet>("greeter", ActorID::Numeric(id)).await;
+        assert!(matches!(sender, Err(OpenChannelError::SystemUnreachable(_))));
+
+        assert!(a.get_delegate().add_route("not a system".to_string(), "b".to_string()).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! # Groups
+//! Actors on any system can be added to named groups with [`Palantir::join_group`](crate::Palantir::join_group),
+//! and [`Palantir::multicast`](crate::Palantir::multicast) sends a message to every member of a group at once.
+//! Group membership is tracked by each palantir instance for itself, so every instance that multicasts to a group
+//! needs to know its members.
+
+use std::collections::HashMap;
+
+use crate::ActorID;
+
+
+
+/// # [`Groups`]
+/// The members of every group, as (system, actor).
+#[derive(Default)]
+pub(crate) struct Groups {
+    /// The members of each group, in the order they joined
+    members: HashMap<String, Vec<(String, ActorID)>>,
+}
+
+impl Groups {
+    /// # [`Groups::join`]
+    /// Adds the given actor on the given system to the given group, returning whether it wasn't already a member.
+    pub fn join(&mut self, group: String, system: String, actor: ActorID) -> bool {
+        let members = self.members.entry(group).or_default();
+
+        if members.iter().any(|(s, a)| *s == system && *a == actor) {
+            return false;
+        }
+
+        members.push((system, actor));
+        true
+    }
+
+    /// # [`Groups::leave`]
+    /// Removes the given actor on the given system from the given group, returning whether it was a member.
+    pub fn leave(&mut self, group: &str, system: &str, actor: &ActorID) -> bool {
+        let Some(members) = self.members.get_mut(group) else {
+            return false;
+        };
+
+        let before = members.len();
+        members.retain(|(s, a)| !(s == system && a == actor));
+        let removed = members.len() != before;
+
+        // Forget empty groups
+        if members.is_empty() {
+            self.members.remove(group);
+        }
+
+        removed
+    }
+
+    /// # [`Groups::members`]
+    /// Returns the members of the given group, which is empty if the group has no members.
+    pub fn members(&self, group: &str) -> Vec<(String, ActorID)> {
+        self.members.get(group).cloned().unwrap_or_default()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, message, ActorContext, Delegate, Handler};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{testkit::two_systems, PalantirSendError};
+    use super::*;
+
+    /// Responds with the name it was created with
+    #[actor]
+    struct Member(&'static str);
+
+    #[message(String)]
+    #[derive(Serialize, Deserialize)]
+    struct Name;
+
+    impl Handler<Name> for Member {
+        async fn handle_message<D: Delegate>(&self, _message: Name, _context: &ActorContext<D>) -> String {
+            self.0.to_string()
+        }
+    }
+
+    #[test]
+    fn membership() {
+        let mut groups = Groups::default();
+
+        assert!(groups.join("g".to_string(), "b".to_string(), ActorID::Numeric(1)));
+        assert!(groups.join("g".to_string(), "a".to_string(), ActorID::Numeric(1)));
+        assert!(!groups.join("g".to_string(), "b".to_string(), ActorID::Numeric(1)));
+        assert_eq!(groups.members("g"), [("b".to_string(), ActorID::Numeric(1)), ("a".to_string(), ActorID::Numeric(1))]);
+        assert!(groups.members("other").is_empty());
+
+        assert!(groups.leave("g", "b", &ActorID::Numeric(1)));
+        assert!(!groups.leave("g", "b", &ActorID::Numeric(1)));
+        assert!(groups.leave("g", "a", &ActorID::Numeric(1)));
+        assert!(groups.members.is_empty());
+    }
+
+    #[tokio::test]
+    async fn multicast() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        let on_a = a.add(Member("on a")).await.unwrap();
+        a.get_delegate().register::<Member, Name, _>(a.get_local::<Member>(on_a).await.unwrap()).await;
+        let on_b = b.add(Member("on b")).await.unwrap();
+        b.get_delegate().register::<Member, Name, _>(b.get_local::<Member>(on_b).await.unwrap()).await;
+
+        let palantir = a.get_delegate();
+        palantir.join_group("g".to_string(), "b".to_string(), ActorID::Numeric(on_b));
+        palantir.join_group("g".to_string(), "a".to_string(), ActorID::Numeric(on_a));
+
+        let responses = palantir.multicast("g", Name).await.unwrap().into_iter()
+            .map(|(system, actor, response)| (system, actor, response.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(responses, [
+            ("b".to_string(), ActorID::Numeric(on_b), "on b".to_string()),
+            ("a".to_string(), ActorID::Numeric(on_a), "on a".to_string()),
+        ]);
+
+        assert!(palantir.multicast("empty", Name).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn multicast_with_missing_members() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        let on_b = b.add(Member("on b")).await.unwrap();
+        b.get_delegate().register::<Member, Name, _>(b.get_local::<Member>(on_b).await.unwrap()).await;
+
+        let palantir = a.get_delegate();
+        palantir.join_group("g".to_string(), "c".to_string(), ActorID::Numeric(on_b));
+        palantir.join_group("g".to_string(), "b".to_string(), ActorID::Numeric(on_b + 1));
+        palantir.join_group("g".to_string(), "b".to_string(), ActorID::Numeric(on_b));
+
+        // Members that can't respond don't hold up the others
+        let responses = palantir.multicast("g", Name).await.unwrap();
+        assert_eq!(responses.len(), 3);
+        assert!(matches!(PalantirSendError::of(responses[0].2.as_ref().unwrap_err()), Some(PalantirSendError::Open(_))));
+        assert!(matches!(PalantirSendError::of(responses[1].2.as_ref().unwrap_err()), Some(PalantirSendError::ActorNotFound)));
+        assert_eq!(responses[2].2.as_ref().unwrap(), "on b");
+    }
+}
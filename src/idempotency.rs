@@ -0,0 +1,229 @@
+//! # Idempotency
+//! Senders can attach an idempotency key to a request with [`Palantir::send_idempotent`](crate::Palantir::send_idempotent).
+//! The receiving palantir instance caches the response to each key for a while, and replays it to any duplicates
+//! instead of handling them again, which makes it safe to retry requests to handlers that aren't idempotent themselves.
+
+use std::{collections::{HashMap, VecDeque}, hash::Hash, time::{Duration, Instant}};
+
+use fluxion::{Message, MessageID};
+use serde::{Deserialize, Serialize};
+
+use crate::{backend::ChannelError, ActorID, Request};
+
+
+
+/// # [`DEFAULT_IDEMPOTENCY_TTL`]
+/// How long responses to keyed requests are cached for by default.
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(60);
+
+/// # [`Keyed`]
+/// A request carrying an idempotency key, which should be handled as a request of the given message type.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Keyed {
+    /// The idempotency key
+    pub key: String,
+    /// The request's actual message type
+    pub message_type: String,
+    /// The serialized message
+    pub data: Vec<u8>,
+}
+
+impl Message for Keyed {
+    /// The response, still serialized
+    type Result = Vec<u8>;
+}
+
+impl MessageID for Keyed {
+    const ID: &'static str = "palantir::idempotency::Keyed";
+}
+
+/// # [`CacheKey`]
+/// Idempotency keys are scoped to the actor and message type they were sent to.
+type CacheKey = (ActorID, String, String);
+
+/// # [`Entry`]
+/// The state of a single idempotency key.
+enum Entry {
+    /// The first request with this key is still being handled, and these duplicates are waiting for its response
+    Pending(Vec<Request>),
+    /// The request was handled, and this is its response
+    Done(Vec<u8>),
+}
+
+/// # [`ResponseCache`]
+/// Caches the responses to keyed requests. Redelivered requests are deduplicated the same way, keyed by their ids
+/// (see [`DedupCache`](crate::dedup::DedupCache)).
+pub(crate) struct ResponseCache<K = CacheKey> {
+    /// How long responses are cached for
+    ttl: Duration,
+    /// The state of every known key
+    entries: HashMap<K, Entry>,
+    /// The keys with cached responses, and when they were cached, oldest first
+    done: VecDeque<(Instant, K)>,
+}
+
+impl<K: Clone + Eq + Hash> ResponseCache<K> {
+    /// # [`ResponseCache::new`]
+    /// Creates an empty [`ResponseCache`] that caches responses for the given time.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+            done: VecDeque::new(),
+        }
+    }
+
+    /// # [`ResponseCache::ttl`]
+    /// Returns how long responses are cached for.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// # [`ResponseCache::set_ttl`]
+    /// Changes how long responses are cached for.
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// # [`ResponseCache::begin`]
+    /// Starts handling a request with the given key.
+    /// If the key has a cached response the request is responded to with it, and if it is still being handled
+    /// the request waits for it. Otherwise, the request is returned, and should be handled and then passed to [`ResponseCache::finish`].
+    pub fn begin(&mut self, key: K, request: Request) -> Option<Request> {
+
+        // Forget expired responses
+        let now = Instant::now();
+        while let Some((cached_at, _)) = self.done.front() {
+            if now.duration_since(*cached_at) < self.ttl {
+                break;
+            }
+
+            if let Some((_, key)) = self.done.pop_front() {
+                self.entries.remove(&key);
+            }
+        }
+
+        match self.entries.get_mut(&key) {
+            Some(Entry::Done(response)) => {
+                let _ = request.respond(Ok(response.clone()));
+                None
+            },
+            Some(Entry::Pending(waiting)) => {
+                waiting.push(request);
+                None
+            },
+            None => {
+                self.entries.insert(key, Entry::Pending(Vec::new()));
+                Some(request)
+            },
+        }
+    }
+
+    /// # [`ResponseCache::finish`]
+    /// Records the response to the request with the given key, responding to every duplicate that was waiting for it.
+    /// Only successful responses are cached, so that a request which failed can be retried.
+    pub fn finish(&mut self, key: K, response: &Result<Vec<u8>, ChannelError>) {
+
+        let waiting = match response {
+            Ok(data) => {
+                self.done.push_back((Instant::now(), key.clone()));
+                self.entries.insert(key, Entry::Done(data.clone()))
+            },
+            Err(_) => self.entries.remove(&key),
+        };
+
+        if let Some(Entry::Pending(waiting)) = waiting {
+            for request in waiting {
+                let _ = request.respond(response.clone());
+            }
+        }
+    }
+
+    /// # [`ResponseCache::abandon`]
+    /// Forgets the request with the given key, which was dropped without a response.
+    /// The duplicates that were waiting for it are dropped unanswered as well, and the next one is handled again.
+    pub fn abandon(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str) -> CacheKey {
+        (ActorID::Numeric(1), "message".to_string(), key.to_string())
+    }
+
+    #[test]
+    fn duplicates_get_the_first_response() {
+        let mut cache = ResponseCache::new(Duration::from_secs(60));
+
+        let (first, _) = Request::new(vec![1]);
+        let first = cache.begin(key("a"), first).expect("the first request should be handled");
+
+        // A duplicate arriving while the first is handled waits for its response
+        let (pending, mut pending_response) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), pending).is_none());
+        assert!(pending_response.try_recv().is_err());
+
+        cache.finish(key("a"), &Ok(vec![2]));
+        drop(first);
+        assert_eq!(pending_response.try_recv().unwrap().unwrap(), vec![2]);
+
+        // One arriving afterwards gets the cached response
+        let (late, mut late_response) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), late).is_none());
+        assert_eq!(late_response.try_recv().unwrap().unwrap(), vec![2]);
+
+        // Other keys are handled separately
+        let (other, _) = Request::new(vec![1]);
+        assert!(cache.begin(key("b"), other).is_some());
+    }
+
+    #[test]
+    fn failures_are_not_cached() {
+        let mut cache = ResponseCache::new(Duration::from_secs(60));
+
+        let (first, _) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), first).is_some());
+        let (pending, mut pending_response) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), pending).is_none());
+
+        cache.finish(key("a"), &Err(ChannelError::RemoteHandler));
+        assert!(matches!(pending_response.try_recv().unwrap(), Err(ChannelError::RemoteHandler)));
+
+        let (retry, _) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), retry).is_some());
+    }
+
+    #[test]
+    fn abandoned_requests() {
+        let mut cache = ResponseCache::new(Duration::from_secs(60));
+
+        let (first, _) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), first).is_some());
+        let (pending, mut pending_response) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), pending).is_none());
+
+        cache.abandon(&key("a"));
+        assert!(matches!(pending_response.try_recv(), Err(tokio::sync::oneshot::error::TryRecvError::Closed)));
+
+        let (retry, _) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), retry).is_some());
+    }
+
+    #[test]
+    fn responses_expire() {
+        let mut cache = ResponseCache::new(Duration::ZERO);
+
+        let (first, _) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), first).is_some());
+        cache.finish(key("a"), &Ok(vec![2]));
+
+        let (again, _) = Request::new(vec![1]);
+        assert!(cache.begin(key("a"), again).is_some());
+    }
+}
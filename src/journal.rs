@@ -0,0 +1,334 @@
+//! # Journal
+//! Palantir can record outgoing requests in a write-ahead journal before sending them with
+//! [`Palantir::send_journaled`](crate::Palantir::send_journaled), and record when they are acknowledged.
+//! After a restart, [`Palantir::unacknowledged`](crate::Palantir::unacknowledged) reports exactly which requests never were,
+//! and [`Palantir::reissue`](crate::Palantir::reissue) can send them again. The journal is stored by a [`JournalStorage`],
+//! of which [`FileJournal`] is the default.
+
+use std::{fs::{File, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex, PoisonError}};
+
+use fluxion::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::{backend::ChannelError, ActorID};
+
+
+
+/// # [`Journaled`]
+/// An outgoing request recorded in the journal.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Journaled {
+    /// The request's id, which is unique within the journal
+    pub id: u64,
+    /// The system the request was sent to
+    pub system: String,
+    /// The actor the request was sent to
+    pub actor: ActorID,
+    /// The request's message type
+    pub message_type: String,
+    /// The serialized message
+    pub data: Vec<u8>,
+}
+
+/// # [`Record`]
+/// A single entry in the journal.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Record {
+    /// # [`Record::Sent`]
+    /// A request is about to be sent.
+    Sent(Journaled),
+    /// # [`Record::Acknowledged`]
+    /// The request with the given id was acknowledged, so it no longer needs to be sent.
+    Acknowledged(u64),
+}
+
+/// # [`JournalStorage`]
+/// Durably stores the journal's records.
+pub trait JournalStorage: Send + Sync + 'static {
+    /// # [`JournalStorage::append`]
+    /// Appends a record to the journal, which should be durable once this returns.
+    ///
+    /// # Errors
+    /// Returns an error if the record couldn't be stored.
+    fn append(&self, record: &Record) -> io::Result<()>;
+
+    /// # [`JournalStorage::records`]
+    /// Returns every record in the journal, oldest first.
+    ///
+    /// # Errors
+    /// Returns an error if the records couldn't be read.
+    fn records(&self) -> io::Result<Vec<Record>>;
+}
+
+/// # [`FileJournal`]
+/// A [`JournalStorage`] that appends records to a file, each prefixed with its length.
+/// A record that was only partially written, for example because the process crashed, is truncated away when the
+/// journal is opened, so that later records are appended after the last complete one.
+pub struct FileJournal {
+    /// The journal's path, which is reread to load its records
+    path: PathBuf,
+    /// The journal file, opened for appending
+    file: Mutex<File>,
+}
+
+impl FileJournal {
+    /// # [`FileJournal::open`]
+    /// Opens the journal at the given path, creating it if it doesn't exist, and truncating any partially written
+    /// record at its end.
+    ///
+    /// # Errors
+    /// Returns an error if the file couldn't be opened or truncated.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        let complete = frames(&std::fs::read(&path)?).map(|frame| frame.len() + 4).sum::<usize>();
+        if complete as u64 != file.metadata()?.len() {
+            file.set_len(complete as u64)?;
+            file.sync_data()?;
+        }
+
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl JournalStorage for FileJournal {
+    fn append(&self, record: &Record) -> io::Result<()> {
+        let data = pot::to_vec(record).map_err(io::Error::other)?;
+        let len = u32::try_from(data.len()).map_err(io::Error::other)?;
+
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // If the write fails partway, cut the partial record off so that later records still follow a complete one
+        let end = file.metadata()?.len();
+        if let Err(e) = file.write_all(&[&len.to_le_bytes()[..], &data].concat()) {
+            let _ = file.set_len(end);
+            return Err(e);
+        }
+        file.sync_data()
+    }
+
+    fn records(&self) -> io::Result<Vec<Record>> {
+        let data = std::fs::read(&self.path)?;
+
+        frames(&data)
+            .map(|frame| pot::from_slice(frame).map_err(io::Error::other))
+            .collect()
+    }
+}
+
+/// # [`frames`]
+/// Splits a journal file into its complete length-prefixed records, stopping at the first partial one.
+fn frames(mut data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let (len, tail) = data.split_first_chunk::<4>()?;
+        let frame = tail.get(..u32::from_le_bytes(*len) as usize)?;
+        data = &tail[frame.len()..];
+        Some(frame)
+    })
+}
+
+/// # [`is_acknowledged`]
+/// Checks whether the given response to a request means the receiving system got it. Requests that failed in transit,
+/// including ones that were interrupted before they reached the system or that a gateway couldn't route to it,
+/// stay unacknowledged, so that they can be reissued.
+pub(crate) fn is_acknowledged(response: &Result<Vec<u8>, ChannelError>) -> bool {
+    !matches!(response, Err(ChannelError::Closed | ChannelError::PeerDisconnected | ChannelError::Timeout { .. }
+        | ChannelError::Interrupted | ChannelError::Unroutable))
+}
+
+/// # [`Reissued`]
+/// The message type used to open channels for reissued requests, whose actual message type isn't known statically.
+pub(crate) struct Reissued;
+
+impl Message for Reissued {
+    /// The response, still serialized
+    type Result = Vec<u8>;
+}
+
+/// # [`Journal`]
+/// Records requests to a [`JournalStorage`], assigning each one an id.
+pub(crate) struct Journal {
+    /// Where records are stored
+    storage: Arc<dyn JournalStorage>,
+    /// The id of the next request
+    next_id: AtomicU64,
+}
+
+impl Journal {
+    /// # [`Journal::new`]
+    /// Creates a journal backed by the given storage, continuing after the ids already in it.
+    pub fn new(storage: Arc<dyn JournalStorage>) -> io::Result<Self> {
+        let next_id = storage.records()?.iter()
+            .filter_map(|record| match record {
+                Record::Sent(journaled) => Some(journaled.id + 1),
+                Record::Acknowledged(_) => None,
+            })
+            .max().unwrap_or_default();
+
+        Ok(Self {
+            storage,
+            next_id: AtomicU64::new(next_id),
+        })
+    }
+
+    /// # [`Journal::sent`]
+    /// Records that a request is about to be sent, returning its id.
+    pub fn sent(&self, system: String, actor: ActorID, message_type: String, data: Vec<u8>) -> io::Result<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        self.storage.append(&Record::Sent(Journaled { id, system, actor, message_type, data }))?;
+
+        Ok(id)
+    }
+
+    /// # [`Journal::acknowledged`]
+    /// Records that the request with the given id was acknowledged.
+    pub fn acknowledged(&self, id: u64) -> io::Result<()> {
+        self.storage.append(&Record::Acknowledged(id))
+    }
+
+    /// # [`Journal::unacknowledged`]
+    /// Returns every request that was sent but never acknowledged, oldest first.
+    pub fn unacknowledged(&self) -> io::Result<Vec<Journaled>> {
+        let records = self.storage.records()?;
+
+        let acknowledged = records.iter()
+            .filter_map(|record| match record {
+                Record::Acknowledged(id) => Some(*id),
+                Record::Sent(_) => None,
+            })
+            .collect::<std::collections::HashSet<_>>();
+
+        Ok(records.into_iter()
+            .filter_map(|record| match record {
+                Record::Sent(journaled) if !acknowledged.contains(&journaled.id) => Some(journaled),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::AtomicBool, time::Duration};
+
+    use fluxion::{message, Fluxion};
+
+    use crate::{serializer::Pot, testkit::{decode, encode, MockBackend}, Palantir};
+    use super::*;
+
+    /// Returns a fresh journal path in the temporary directory, removing anything left from a previous run.
+    fn path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("palantir-journal-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn sent(id: u64) -> Record {
+        Record::Sent(Journaled {
+            id,
+            system: "peer".to_string(),
+            actor: ActorID::Named("actor".to_string()),
+            message_type: "message".to_string(),
+            data: vec![1, 2, 3],
+        })
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = path("round-trip");
+        let journal = Journal::new(Arc::new(FileJournal::open(&path).unwrap())).unwrap();
+
+        let first = journal.sent("peer".to_string(), ActorID::Numeric(1), "a".to_string(), vec![1]).unwrap();
+        let second = journal.sent("peer".to_string(), ActorID::Numeric(2), "b".to_string(), vec![2]).unwrap();
+        journal.acknowledged(first).unwrap();
+        drop(journal);
+
+        let journal = Journal::new(Arc::new(FileJournal::open(&path).unwrap())).unwrap();
+        let unacknowledged = journal.unacknowledged().unwrap();
+        assert_eq!(unacknowledged.len(), 1);
+        assert_eq!(unacknowledged[0].id, second);
+        assert_eq!(unacknowledged[0].data, vec![2]);
+        assert_eq!(journal.sent("peer".to_string(), ActorID::Numeric(3), "c".to_string(), vec![3]).unwrap(), second + 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn torn_tail() {
+        let path = path("torn-tail");
+        FileJournal::open(&path).unwrap().append(&sent(0)).unwrap();
+
+        // Simulate a crash partway through writing a record
+        let data = pot::to_vec(&sent(1)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&u32::try_from(data.len()).unwrap().to_le_bytes()).unwrap();
+        file.write_all(&data[..data.len() / 2]).unwrap();
+        drop(file);
+
+        let storage = FileJournal::open(&path).unwrap();
+        storage.append(&sent(2)).unwrap();
+
+        let ids = storage.records().unwrap().into_iter()
+            .map(|record| match record {
+                Record::Sent(journaled) => journaled.id,
+                Record::Acknowledged(id) => id,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 2]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn failures_in_transit_are_unacknowledged() {
+        for error in [ChannelError::Closed, ChannelError::PeerDisconnected, ChannelError::Timeout { elapsed: Duration::ZERO },
+            ChannelError::Interrupted, ChannelError::Unroutable] {
+            assert!(!is_acknowledged(&Err(error)));
+        }
+
+        for error in [ChannelError::HandlerNotFound, ChannelError::RemoteHandler, ChannelError::Overloaded, ChannelError::Expired] {
+            assert!(is_acknowledged(&Err(error)));
+        }
+        assert!(is_acknowledged(&Ok(Vec::new())));
+    }
+
+    #[message(u32)]
+    #[derive(Serialize, Deserialize)]
+    struct Value(u32);
+
+    #[tokio::test]
+    async fn reissue_interrupted() {
+        let path = path("reissue-interrupted");
+
+        // The first request is interrupted, and the ones after it go through
+        let interrupted = Arc::new(AtomicBool::new(true));
+        let interrupted_clone = interrupted.clone();
+        let backend = MockBackend::new(move |_, _, _, data| if interrupted_clone.swap(false, Ordering::Relaxed) {
+            Err(ChannelError::Interrupted)
+        } else {
+            Ok(data.to_vec())
+        });
+        let system = Fluxion::new("a", Palantir::new("a".to_string(), backend).unwrap());
+        let palantir = system.get_delegate();
+        palantir.set_journal(Some(Arc::new(FileJournal::open(&path).unwrap()))).unwrap();
+
+        assert!(palantir.send_journaled("b", ActorID::Numeric(1), Value(7)).await.is_err());
+
+        let unacknowledged = palantir.unacknowledged().unwrap();
+        assert_eq!(unacknowledged.len(), 1);
+        assert_eq!(decode::<Pot, Value>(&unacknowledged[0].data).0, 7);
+
+        let response = palantir.reissue(&unacknowledged[0]).await.unwrap();
+        assert_eq!(response, encode::<Pot>(&Value(7)));
+        assert!(palantir.unacknowledged().unwrap().is_empty());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
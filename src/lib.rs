@@ -1,47 +1,321 @@
 //! # Palantir
 
-#[warn(clippy::pedantic)]
-#[allow(clippy::module_name_repetitions)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::module_name_repetitions)]
 
 
 pub mod backend;
 
-mod request;
+pub mod request;
+pub use request::Request;
+
 pub mod actor_id;
 pub use actor_id::ActorID;
 
-use backend::{Backend, Channel};
-use fluxion::{Actor, Delegate, Handler, Identifier, IndeterminateMessage, LocalRef, MessageSender};
-use request::Request;
+pub mod event;
+pub use event::Event;
+
+mod federation;
+use federation::{Forward, Gateways, Link};
+
+pub mod system_id;
+use system_id::SystemIdError;
+
+pub mod pattern;
+pub use pattern::Routed;
+
+pub mod stats;
+pub use stats::{ActorMetrics, ActorStats, LatencyHistogram};
+use stats::{Outcome, Tracker};
+
+mod dedup;
+use dedup::DedupCache;
+
+pub mod idempotency;
+use idempotency::{Keyed, ResponseCache};
+
+pub mod outbox;
+pub use outbox::OutboxConfig;
+use outbox::Outbox;
+
+mod ttl;
+use ttl::Expiring;
+
+mod group;
+use group::Groups;
+
+pub mod scatter;
+pub use scatter::{Gathered, Quorum};
+
+pub mod remote_ref;
+pub use remote_ref::RemoteRef;
+
+pub mod bounded;
+pub use bounded::{BoundedSender, TrySendError};
+
+pub mod journal;
+use journal::{Journal, JournalStorage, Journaled, Reissued};
+
+pub mod probe;
+pub use probe::ProbeStats;
+use probe::Probe;
+
+pub mod config;
+pub use config::Config;
+
+mod query;
+use query::Query;
+
+mod fanout;
+use fanout::Broadcast;
+
+pub mod registration;
+pub use registration::{OverflowPolicy, RegistrationBuilder, RegistrationConfig};
+
+pub mod schema;
+use schema::Handshake;
+
+mod trace;
+use trace::{debug, debug_span, info, info_span, warn, Instrument};
+
+pub mod supervision;
+pub use supervision::{Failure, PanicPolicy};
+use supervision::{Control, Supervisor};
+
+pub mod serializer;
+use serializer::{Pot, Serializer};
+
+pub mod timeout;
+pub use timeout::SendTimeout;
+use timeout::Timeouts;
+
+pub mod retry;
+pub use retry::{RetryOn, RetryPolicy};
+
+pub mod limit;
+use limit::PayloadLimits;
+
+pub mod error;
+pub use error::PalantirSendError;
+
+pub mod middleware;
+pub use middleware::Middleware;
+use middleware::Chain;
+
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+
+use backend::{Backend, ChannelError, OpenChannelError, PeerEvent};
+use fluxion::{Actor, Delegate, Handler, Identifier, IndeterminateMessage, LocalRef, Message, MessageID, MessageSendError, MessageSender};
 use serde::{Deserialize, Serialize};
 
 
 
 
-use std::{collections::HashMap, error::Error, marker::PhantomData, sync::Arc};
-use tokio::{sync::{mpsc, RwLock}, task::JoinSet};
+use std::{any::Any, collections::HashMap, future::Future, marker::PhantomData, ops::ControlFlow, pin::Pin, sync::{atomic::{AtomicBool, Ordering}, Arc, Weak}, time::{Duration, Instant}};
+use tokio::{sync::{broadcast, mpsc, watch, RwLock, Semaphore}, task::JoinSet};
+
+
+/// # [`Handle`]
+/// Handles a single request for a registration, given the actor it was sent to: deserializes it, relays it to the actor,
+/// and responds with the actor's response.
+type Handle = Arc<dyn Fn(ActorID, Request) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
 
+/// # [`Queued`]
+/// A request queued for a relay task, alongside the state and handler of the registration it was dispatched to.
+/// Relay tasks may be shared by several registrations of the same actor (see [`RegistrationBuilder`]).
+struct Queued {
+    /// The actor the request was sent to
+    target: ActorID,
+    /// The request
+    request: Request,
+    /// The registration's statistics
+    stats: Arc<Tracker>,
+    /// Whether the registration is paused or removed
+    control: Arc<Control>,
+    /// Handles the request
+    handle: Handle,
+}
+
+/// # [`Registration`]
+/// The handle that inbound requests are dispatched to a registered actor through.
+#[derive(Clone)]
+struct Registration {
+    /// The registered actor's id
+    actor: u64,
+    /// Queues requests for the registration's relay task
+    sender: mpsc::Sender<Queued>,
+    /// Handles the registration's requests once the relay task takes them
+    handle: Handle,
+    /// The fingerprint of the registration's message type, if it could be computed
+    fingerprint: Option<u64>,
+    /// What happens to requests once the relay task's queue is full
+    overflow: OverflowPolicy,
+    /// The registration's statistics
+    stats: Arc<Tracker>,
+    /// Whether the registration is paused or removed
+    control: Arc<Control>,
+}
+
+/// # [`ActorHandlers`]
+/// The registrations of individual actors, keyed by the actor's id and the message type.
+type ActorHandlers = RwLock<HashMap<(u64, String), Registration>>;
+
+/// # [`PatternHandlers`]
+/// The registrations of actor name patterns, as (pattern, message type, registration), in registration order.
+type PatternHandlers = RwLock<Vec<(String, String, Registration)>>;
+
+/// # [`unregister_actor`]
+/// Removes the given actor's registration for the given message type if it matches the filter, alongside the actor's local sender
+/// for the message type, and publishes the removal. Returns whether a registration was removed.
+///
+/// # Panics
+/// Panics if the local senders lock is poisoned, which should never happen.
+async fn unregister_actor(actor_handlers: &ActorHandlers, local_senders: &std::sync::RwLock<LocalSenders>, events: &broadcast::Sender<Event>,
+    actor: u64, message_type: &'static str, filter: impl FnOnce(&Registration) -> bool) -> bool {
+
+    let key = (actor, message_type.to_string());
+
+    let registration = {
+        let mut actor_handlers = actor_handlers.write().await;
+        if !actor_handlers.get(&key).is_some_and(filter) {
+            return false;
+        }
+        actor_handlers.remove(&key)
+    };
+
+    local_senders.write().expect("local senders lock should never be poisoned")
+        .remove(&key);
+
+    if let Some(registration) = registration {
+        registration.control.remove();
+    }
+    let _ = events.send(Event::RegistrationRemoved { actor, message_type });
+
+    true
+}
+
+/// # [`Deregistration`]
+/// Removes a registration once its handler has panicked too often (see [`PanicPolicy::Deregister`]),
+/// the same way as [`Palantir::unregister`] and [`Palantir::unregister_pattern`].
+/// It only refers to the instance's registrations weakly, as they hold the handler that holds this.
+struct Deregistration {
+    /// The instance's actor registrations
+    actor_handlers: Weak<ActorHandlers>,
+    /// The instance's pattern registrations
+    pattern_handlers: Weak<PatternHandlers>,
+    /// The instance's local senders
+    local_senders: Weak<std::sync::RwLock<LocalSenders>>,
+    /// The event bus removals are published on
+    events: broadcast::Sender<Event>,
+}
+
+impl Deregistration {
+    /// # [`Deregistration::run`]
+    /// Removes the registration with the given control, which belongs to the given actor and message type.
+    async fn run(&self, control: &Arc<Control>, actor: u64, message_type: &'static str) {
+        control.remove();
+
+        // The instance is gone, and its registrations with it
+        let (Some(actor_handlers), Some(pattern_handlers), Some(local_senders)) =
+            (self.actor_handlers.upgrade(), self.pattern_handlers.upgrade(), self.local_senders.upgrade()) else {
+            return;
+        };
+
+        if unregister_actor(&actor_handlers, &local_senders, &self.events, actor, message_type,
+            |registration| Arc::ptr_eq(&registration.control, control)).await {
+            return;
+        }
+
+        let mut removed = false;
+        pattern_handlers.write().await
+            .retain(|(_, _, registration)| {
+                let matches = Arc::ptr_eq(&registration.control, control);
+                removed |= matches;
+                !matches
+            });
+
+        if removed {
+            let _ = self.events.send(Event::RegistrationRemoved { actor, message_type });
+        }
+    }
+}
 
 /// # [`Palantir`]
-/// Palantir provides a [`Delegate`] implementation for [`fluxion`] that is generic over [`Backends`].
+/// Palantir provides a [`Delegate`] implementation for [`fluxion`] that is generic over [`Backends`]
+/// and the [`Serializer`] used for messages, which defaults to [`Pot`].
 /// Generally, this is used to connect a [`fluxion`] system to a network.
-pub struct Palantir<B> {
+pub struct Palantir<B, S = Pot> {
     /// This system's id
     system_id: String,
     /// The backend that is used by this palantir instance
     /// to communicate with other systems.
-    backend: B,
+    backend: Arc<B>,
     /// A hashmap of message handling channels for actors
-    actor_handlers: RwLock<HashMap<(u64, String), mpsc::Sender<Request>>>,
+    actor_handlers: Arc<ActorHandlers>,
+    /// Message handling channels for actor name patterns, as (pattern, message type, channel), in registration order
+    pattern_handlers: Arc<PatternHandlers>,
+    /// The statistics of every registered actor and message type
+    stats: std::sync::Mutex<HashMap<(u64, String), Arc<Tracker>>>,
+    /// The responses to recently received requests, keyed by their ids, if deduplication is enabled
+    dedup: Arc<std::sync::Mutex<Option<DedupCache>>>,
+    /// The cached responses to requests with idempotency keys
+    idempotency: Arc<std::sync::Mutex<ResponseCache>>,
+    /// Holds messages for unreachable systems, if enabled
+    outbox: Arc<Outbox>,
     /// A join set containing tasks spawned by this palantir instance
     join_set: Arc<std::sync::Mutex<JoinSet<()>>>,
+    /// A join set containing the tasks handling inbound requests, which are aborted once this instance leaves the cluster
+    handlers: Arc<std::sync::Mutex<JoinSet<()>>>,
+    /// The sending half of the event bus
+    events: broadcast::Sender<Event>,
+    /// The gateways that requests to other federation zones are routed through
+    gateways: Arc<std::sync::RwLock<Gateways>>,
+    /// The members of every actor group
+    groups: std::sync::RwLock<Groups>,
+    /// The journal outgoing requests are recorded in, if enabled
+    journal: std::sync::RwLock<Option<Arc<Journal>>>,
+    /// Whether this instance is leaving the cluster, and no longer accepts requests
+    leaving: AtomicBool,
+    /// Set once this instance has left the cluster, which stops [`Palantir::serve`]
+    left: watch::Sender<bool>,
+    /// What happens to registrations whose handlers panic
+    panic_policy: Arc<std::sync::RwLock<PanicPolicy>>,
+    /// Notifies the application of repeated failures
+    supervisor: Arc<Supervisor>,
+    /// How long senders wait for responses
+    timeouts: std::sync::RwLock<Timeouts>,
+    /// How senders retry failed requests, if they do
+    retry_policy: std::sync::RwLock<Option<RetryPolicy>>,
+    /// The largest inbound payloads accepted
+    payload_limits: std::sync::RwLock<PayloadLimits>,
+    /// Whether senders check the schemas of their message types when opening channels
+    schema_validation: AtomicBool,
+    /// Whether foreign identifiers for actors on this system resolve to the actors directly
+    local_resolution: AtomicBool,
+    /// Senders that go directly to the registered actors, keyed by the actor's id and the message type
+    local_senders: Arc<std::sync::RwLock<LocalSenders>>,
+    /// The middleware messages pass through
+    middleware: Arc<Chain>,
+    /// Held while a whole [`Config`] is applied or read, so that neither sees a partially applied one
+    config_lock: std::sync::Mutex<()>,
+    /// The serializer, which is only used statically
+    _serializer: PhantomData<S>,
+}
+
+impl<B, S> AsRef<Palantir<B, S>> for Palantir<B, S> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
 }
 
-impl<B> Drop for Palantir<B> {
+impl<B, S> Drop for Palantir<B, S> {
     fn drop(&mut self) {
-        match self.join_set.lock() {
-            Ok(mut js) => js.abort_all(),
-            Err(e) => e.into_inner().abort_all(),
+        for join_set in [&self.join_set, &self.handlers] {
+            match join_set.lock() {
+                Ok(mut js) => js.abort_all(),
+                Err(e) => e.into_inner().abort_all(),
+            }
         }
     }
 }
@@ -49,161 +323,2467 @@ impl<B> Drop for Palantir<B> {
 
 impl<B> Palantir<B> {
     /// # [`Palantir::new`]
-    /// Creates a new [`Palantir`] instance with the given system id and backend.
-    pub fn new(system_id: String, backend: B) -> Self {
-        Self {
+    /// Creates a new [`Palantir`] instance with the given system id and backend, which serializes messages with [`Pot`].
+    /// 
+    /// # Errors
+    /// Returns a [`SystemIdError`] if the system id is invalid (see [`system_id::validate`]).
+    pub fn new(system_id: String, backend: B) -> Result<Self, SystemIdError> {
+        Self::with_serializer(system_id, backend)
+    }
+}
+
+impl<B, S: Serializer> Palantir<B, S> {
+    /// # [`Palantir::with_serializer`]
+    /// Creates a new [`Palantir`] instance with the given system id and backend, which serializes messages with `S`.
+    /// Every system it talks to has to use the same serializer.
+    /// 
+    /// # Errors
+    /// Returns a [`SystemIdError`] if the system id is invalid (see [`system_id::validate`]).
+    pub fn with_serializer(system_id: String, backend: B) -> Result<Self, SystemIdError> {
+
+        system_id::validate(&system_id)?;
+
+        Ok(Self {
+            gateways: Arc::new(std::sync::RwLock::new(Gateways::new(system_id.clone()))),
             system_id,
-            backend,
-            actor_handlers: RwLock::default(),
+            backend: Arc::new(backend),
+            actor_handlers: Arc::default(),
+            pattern_handlers: Arc::default(),
+            stats: std::sync::Mutex::default(),
+            dedup: Arc::default(),
+            idempotency: Arc::new(std::sync::Mutex::new(ResponseCache::new(idempotency::DEFAULT_IDEMPOTENCY_TTL))),
+            outbox: Arc::default(),
             join_set: Arc::default(),
+            handlers: Arc::default(),
+            events: broadcast::channel(256).0,
+            groups: std::sync::RwLock::default(),
+            journal: std::sync::RwLock::default(),
+            leaving: AtomicBool::new(false),
+            left: watch::channel(false).0,
+            panic_policy: Arc::default(),
+            supervisor: Arc::default(),
+            timeouts: std::sync::RwLock::default(),
+            retry_policy: std::sync::RwLock::default(),
+            payload_limits: std::sync::RwLock::default(),
+            schema_validation: AtomicBool::new(false),
+            local_resolution: AtomicBool::new(false),
+            local_senders: Arc::default(),
+            middleware: Arc::default(),
+            config_lock: std::sync::Mutex::default(),
+            _serializer: PhantomData,
+        })
+    }
+
+    /// # [`Palantir::add_gateway`]
+    /// Routes requests to systems in the given federation zone through the given gateway system,
+    /// returning the zone's previous gateway. The zone `eu.west` contains both `eu.west` and `eu.west.node1`,
+    /// and the most specific zone containing a system is used. This only affects senders opened afterwards.
+    /// 
+    /// # Errors
+    /// Returns a [`SystemIdError`] if either the zone or the gateway are not valid system ids.
+    /// 
+    /// # Panics
+    /// Panics if the gateways lock is poisoned, which should never happen.
+    pub fn add_gateway(&self, zone: String, gateway: String) -> Result<Option<String>, SystemIdError> {
+
+        system_id::validate(&zone)?;
+        system_id::validate(&gateway)?;
+
+        Ok(self.gateways.write().expect("gateways lock should never be poisoned")
+            .insert(zone, gateway))
+    }
+
+    /// # [`Palantir::remove_gateway`]
+    /// Stops routing requests to the given federation zone through a gateway, returning the removed gateway.
+    /// 
+    /// # Panics
+    /// Panics if the gateways lock is poisoned, which should never happen.
+    pub fn remove_gateway(&self, zone: &str) -> Option<String> {
+        self.gateways.write().expect("gateways lock should never be poisoned")
+            .remove(zone)
+    }
+
+    /// # [`Palantir::add_route`]
+    /// Routes requests to the given system to the given backend address (e.g. a socket address or peer id),
+    /// returning the system's previous address. Systems without a route are passed to the backend by their id.
+    /// Gateways are routed like any other system. This only affects senders opened afterwards.
+    /// 
+    /// # Errors
+    /// Returns a [`SystemIdError`] if the system is not a valid system id.
+    /// 
+    /// # Panics
+    /// Panics if the gateways lock is poisoned, which should never happen.
+    pub fn add_route(&self, system: String, address: String) -> Result<Option<String>, SystemIdError> {
+
+        system_id::validate(&system)?;
+
+        Ok(self.gateways.write().expect("gateways lock should never be poisoned")
+            .insert_route(system, address))
+    }
+
+    /// # [`Palantir::remove_route`]
+    /// Stops routing requests to the given system to a backend address, returning the removed address.
+    /// 
+    /// # Panics
+    /// Panics if the gateways lock is poisoned, which should never happen.
+    pub fn remove_route(&self, system: &str) -> Option<String> {
+        self.gateways.write().expect("gateways lock should never be poisoned")
+            .remove_route(system)
+    }
+
+    /// # [`Palantir::routes`]
+    /// Returns the backend address of every routed system, keyed by system.
+    /// 
+    /// # Panics
+    /// Panics if the gateways lock is poisoned, which should never happen.
+    #[must_use]
+    pub fn routes(&self) -> HashMap<String, String> {
+        self.gateways.read().expect("gateways lock should never be poisoned")
+            .routes().clone()
+    }
+
+    /// # [`Palantir::join_group`]
+    /// Adds the given actor on the given system to the given group, so that it receives messages multicast to the group
+    /// (see [`Palantir::multicast`]). Returns whether the actor wasn't already a member.
+    /// 
+    /// # Panics
+    /// Panics if the groups lock is poisoned, which should never happen.
+    pub fn join_group(&self, group: String, system: String, actor: ActorID) -> bool {
+        self.groups.write().expect("groups lock should never be poisoned")
+            .join(group, system, actor)
+    }
+
+    /// # [`Palantir::leave_group`]
+    /// Removes the given actor on the given system from the given group, returning whether it was a member.
+    /// 
+    /// # Panics
+    /// Panics if the groups lock is poisoned, which should never happen.
+    pub fn leave_group(&self, group: &str, system: &str, actor: &ActorID) -> bool {
+        self.groups.write().expect("groups lock should never be poisoned")
+            .leave(group, system, actor)
+    }
+
+    /// # [`Palantir::group_members`]
+    /// Returns the members of the given group as (system, actor), in the order they joined.
+    /// 
+    /// # Panics
+    /// Panics if the groups lock is poisoned, which should never happen.
+    #[must_use]
+    pub fn group_members(&self, group: &str) -> Vec<(String, ActorID)> {
+        self.groups.read().expect("groups lock should never be poisoned")
+            .members(group)
+    }
+
+    /// # [`Palantir::set_dedup_window`]
+    /// Enables deduplication of inbound requests, with the given window, or disables it if [`None`].
+    /// While enabled, a request delivered with the same id as one that was responded to within the window (see [`Request::with_id`])
+    /// is not handled again, and is instead responded to with the earlier request's response. One delivered while the earlier request
+    /// is still being handled waits for its response. Senders give every retry of a request the same id, so this stops retries from
+    /// being handled twice while still returning the actor's response to the sender, but only over backends that deliver the ids
+    /// (see [`Channel::request_with_id`](backend::Channel::request_with_id)). Like with [`Palantir::send_idempotent`],
+    /// only successful responses are remembered, so a request that failed is handled again when it is retried.
+    /// Changing the window keeps the responses that were already remembered, while disabling deduplication forgets them.
+    /// 
+    /// # Panics
+    /// Panics if the dedup mutex is poisoned, which should never happen.
+    pub fn set_dedup_window(&self, window: Option<Duration>) {
+        let mut dedup = self.dedup.lock().expect("dedup mutex should never be poisoned");
+
+        // Keep remembering the ids that were already seen if we only change the window
+        match (dedup.as_mut(), window) {
+            (Some(dedup), Some(window)) => dedup.set_ttl(window),
+            (_, window) => *dedup = window.map(DedupCache::new),
+        }
+    }
+
+    /// # [`Palantir::set_idempotency_ttl`]
+    /// Sets how long the responses to inbound requests with idempotency keys (see [`Palantir::send_idempotent`]) are cached for.
+    /// Defaults to [`idempotency::DEFAULT_IDEMPOTENCY_TTL`].
+    /// 
+    /// # Panics
+    /// Panics if the idempotency mutex is poisoned, which should never happen.
+    pub fn set_idempotency_ttl(&self, ttl: Duration) {
+        self.idempotency.lock().expect("idempotency mutex should never be poisoned")
+            .set_ttl(ttl);
+    }
+
+    /// # [`Palantir::set_outbox`]
+    /// Enables holding messages for systems that can't currently be reached with the given configuration, or disables it if [`None`].
+    /// While enabled, senders can be opened to unreachable systems, and messages sent to them are held until the system
+    /// can be reached again, or until they expire. This includes messages whose peer disconnects while they are being sent.
+    /// Messages that are already held are not affected.
+    pub fn set_outbox(&self, config: Option<OutboxConfig>) {
+        self.outbox.set_config(config);
+    }
+
+    /// # [`Palantir::remote_ref`]
+    /// Creates a [`RemoteRef`] to the given local actor, which other systems can resolve to send it messages of type `M`.
+    #[must_use]
+    pub fn remote_ref<M: MessageID>(&self, actor: u64) -> RemoteRef<M> {
+        RemoteRef::new(self.system_id.clone(), ActorID::Numeric(actor))
+    }
+
+    /// # [`Palantir::set_journal`]
+    /// Records requests sent with [`Palantir::send_journaled`] to the given storage, or stops recording them if [`None`].
+    /// Request ids continue after the ones already in the storage.
+    /// 
+    /// # Errors
+    /// Returns an error if the storage's existing records couldn't be read, in which case the journal is left unchanged.
+    /// 
+    /// # Panics
+    /// Panics if the journal lock is poisoned, which should never happen.
+    pub fn set_journal(&self, storage: Option<Arc<dyn JournalStorage>>) -> std::io::Result<()> {
+        let journal = storage.map(Journal::new).transpose()?.map(Arc::new);
+
+        *self.journal.write().expect("journal lock should never be poisoned") = journal;
+
+        Ok(())
+    }
+
+    /// # [`Palantir::unacknowledged`]
+    /// Returns every request in the journal that was sent but never acknowledged, oldest first.
+    /// A request is acknowledged once the receiving system responds to it, even if it failed to handle it.
+    /// Returns no requests if the journal is disabled.
+    /// 
+    /// # Errors
+    /// Returns an error if the journal couldn't be read.
+    /// 
+    /// # Panics
+    /// Panics if the journal lock is poisoned, which should never happen.
+    pub fn unacknowledged(&self) -> std::io::Result<Vec<Journaled>> {
+        let journal = self.journal.read().expect("journal lock should never be poisoned").clone();
+
+        journal.map_or_else(|| Ok(Vec::new()), |journal| journal.unacknowledged())
+    }
+
+    /// # [`Palantir::discard`]
+    /// Marks the journaled request with the given id as acknowledged, so that it is no longer reported
+    /// by [`Palantir::unacknowledged`]. Does nothing if the journal is disabled.
+    /// 
+    /// # Errors
+    /// Returns an error if the journal couldn't be written to.
+    /// 
+    /// # Panics
+    /// Panics if the journal lock is poisoned, which should never happen.
+    pub fn discard(&self, id: u64) -> std::io::Result<()> {
+        let journal = self.journal.read().expect("journal lock should never be poisoned").clone();
+
+        journal.map_or(Ok(()), |journal| journal.acknowledged(id))
+    }
+
+    /// # [`Palantir::set_panic_policy`]
+    /// Sets what happens to registrations whose handlers panic while handling a message. Defaults to [`PanicPolicy::Continue`].
+    /// Every panic is responded to with [`ChannelError::RemoteHandler`] and published as an [`Event::HandlerPanicked`] regardless.
+    /// 
+    /// # Panics
+    /// Panics if the panic policy lock is poisoned, which should never happen.
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        *self.panic_policy.write().expect("panic policy lock should never be poisoned") = policy;
+    }
+
+    /// # [`Palantir::on_repeated_failure`]
+    /// Calls the given callback whenever a registration's handler panics, or an actor's relay task panics and is restarted,
+    /// once it has done so at least `after` times. This replaces any previous callback. The callback is called from within
+    /// palantir's tasks, so it should return quickly.
+    pub fn on_repeated_failure(&self, after: usize, callback: impl Fn(&Failure) + Send + Sync + 'static) {
+        self.supervisor.set(Some((after, Arc::new(callback))));
+    }
+
+    /// # [`Palantir::clear_failure_callback`]
+    /// Stops calling the callback set with [`Palantir::on_repeated_failure`].
+    pub fn clear_failure_callback(&self) {
+        self.supervisor.set(None);
+    }
+
+    /// # [`Palantir::set_request_timeout`]
+    /// Sets how long senders wait for a response before failing with a [`SendTimeout`], or makes them wait forever if [`None`],
+    /// which is the default. Message types with their own timeout (see [`Palantir::set_message_timeout`]) keep it.
+    /// This only affects senders opened afterwards.
+    /// 
+    /// # Panics
+    /// Panics if the timeouts lock is poisoned, which should never happen.
+    pub fn set_request_timeout(&self, timeout: Option<Duration>) {
+        self.timeouts.write().expect("timeouts lock should never be poisoned").default = timeout;
+    }
+
+    /// # [`Palantir::set_message_timeout`]
+    /// Sets how long senders of the given message type wait for a response before failing with a [`SendTimeout`],
+    /// overriding the default set by [`Palantir::set_request_timeout`]. If [`None`], the message type uses the default again.
+    /// This only affects senders opened afterwards.
+    /// 
+    /// # Panics
+    /// Panics if the timeouts lock is poisoned, which should never happen.
+    pub fn set_message_timeout<M: MessageID>(&self, timeout: Option<Duration>) {
+        let mut timeouts = self.timeouts.write().expect("timeouts lock should never be poisoned");
+
+        match timeout {
+            Some(timeout) => timeouts.overrides.insert(M::ID.to_string(), timeout),
+            None => timeouts.overrides.remove(M::ID),
+        };
+    }
+
+    /// # [`Palantir::set_max_payload_size`]
+    /// Sets the largest inbound request accepted in bytes, or accepts requests of any size if [`None`], which is the default.
+    /// Message types with their own limit (see [`Palantir::set_message_max_payload_size`]) keep it. See [`limit`].
+    /// 
+    /// # Panics
+    /// Panics if the payload limits lock is poisoned, which should never happen.
+    pub fn set_max_payload_size(&self, limit: Option<usize>) {
+        self.payload_limits.write().expect("payload limits lock should never be poisoned").default = limit;
+    }
+
+    /// # [`Palantir::set_message_max_payload_size`]
+    /// Sets the largest inbound request of the given message type accepted in bytes, overriding the default set by
+    /// [`Palantir::set_max_payload_size`]. If [`None`], the message type uses the default again.
+    /// 
+    /// # Panics
+    /// Panics if the payload limits lock is poisoned, which should never happen.
+    pub fn set_message_max_payload_size<M: MessageID>(&self, limit: Option<usize>) {
+        let mut limits = self.payload_limits.write().expect("payload limits lock should never be poisoned");
+
+        match limit {
+            Some(limit) => limits.overrides.insert(M::ID.to_string(), limit),
+            None => limits.overrides.remove(M::ID),
+        };
+    }
+
+    /// # [`Palantir::max_payload_size`]
+    /// Returns the largest inbound request of the given message type accepted in bytes, or [`None`] if it is unlimited.
+    /// Backends that frame requests can use this to reject oversized frames before reading them.
+    /// 
+    /// # Panics
+    /// Panics if the payload limits lock is poisoned, which should never happen.
+    #[must_use]
+    pub fn max_payload_size(&self, message_type: &str) -> Option<usize> {
+        self.payload_limits.read().expect("payload limits lock should never be poisoned").get(message_type)
+    }
+
+    /// # [`Palantir::set_retry_policy`]
+    /// Sets how senders retry requests that fail, or makes them not retry at all if [`None`], which is the default.
+    /// Each attempt waits for the request timeout on its own (see [`Palantir::set_request_timeout`]).
+    /// This only affects senders opened afterwards.
+    /// 
+    /// # Panics
+    /// Panics if the retry policy lock is poisoned, which should never happen.
+    pub fn set_retry_policy(&self, policy: Option<RetryPolicy>) {
+        *self.retry_policy.write().expect("retry policy lock should never be poisoned") = policy;
+    }
+
+    /// # [`Palantir::set_schema_validation`]
+    /// Sets whether senders check that the actor they send to has the same schema for their message type (see [`schema`]),
+    /// which is disabled by default. If enabled, sends fail with [`PalantirSendError::SchemaMismatch`] instead of sending
+    /// messages the actor can't deserialize. This only affects senders opened afterwards.
+    pub fn set_schema_validation(&self, enabled: bool) {
+        self.schema_validation.store(enabled, Ordering::Relaxed);
+    }
+
+    /// # [`Palantir::set_local_resolution`]
+    /// Sets whether foreign identifiers naming this system (e.g. `Identifier::Foreign(id, "this-system")`) resolve to the
+    /// registered actor directly, which is disabled by default. If enabled, [`Delegate::get_actor`] returns a sender that
+    /// sends to the actor without serializing anything or going through the backend, so calling code can address local
+    /// and foreign actors the same way. Only actors registered under exact ids are resolved like this; everything else,
+    /// including pattern registrations, still goes through the backend.
+    /// 
+    /// As these senders skip palantir entirely, their messages aren't counted in the registration's statistics,
+    /// aren't subject to its [`RegistrationConfig`] or [`PanicPolicy`], and still reach the actor after it is unregistered.
+    pub fn set_local_resolution(&self, enabled: bool) {
+        self.local_resolution.store(enabled, Ordering::Relaxed);
+    }
+
+    /// # [`Palantir::add_middleware`]
+    /// Adds middleware that every message this instance sends and receives passes through, after any middleware added before it.
+    /// This applies to existing senders as well. See [`middleware`] for the order middleware runs in.
+    pub fn add_middleware(&self, middleware: impl Middleware) {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    /// # [`Palantir::clear_middleware`]
+    /// Removes all middleware from this instance.
+    pub fn clear_middleware(&self) {
+        self.middleware.clear();
+    }
+
+    /// # [`Palantir::config`]
+    /// Returns this instance's current runtime settings.
+    /// 
+    /// # Panics
+    /// Panics if any of the settings' locks are poisoned, which should never happen.
+    #[must_use]
+    pub fn config(&self) -> Config {
+        let _config = self.config_lock.lock().expect("config mutex should never be poisoned");
+        let gateways = self.gateways.read().expect("gateways lock should never be poisoned");
+        let timeouts = self.timeouts.read().expect("timeouts lock should never be poisoned");
+        let payload_limits = self.payload_limits.read().expect("payload limits lock should never be poisoned");
+
+        Config {
+            dedup_window: self.dedup.lock().expect("dedup mutex should never be poisoned")
+                .as_ref().map(DedupCache::ttl),
+            idempotency_ttl: self.idempotency.lock().expect("idempotency mutex should never be poisoned")
+                .ttl(),
+            outbox: self.outbox.config(),
+            gateways: gateways.rules().clone(),
+            routes: gateways.routes().clone(),
+            request_timeout: timeouts.default,
+            message_timeouts: timeouts.overrides.clone(),
+            retry_policy: self.retry_policy.read().expect("retry policy lock should never be poisoned").clone(),
+            max_payload_size: payload_limits.default,
+            message_max_payload_sizes: payload_limits.overrides.clone(),
+            schema_validation: self.schema_validation.load(Ordering::Relaxed),
+            local_resolution: self.local_resolution.load(Ordering::Relaxed),
+        }
+    }
+
+    /// # [`Palantir::apply_config`]
+    /// Changes this instance's runtime settings while it is running. Each setting is applied the same way as its
+    /// individual setter, so recently seen request ids and cached responses are kept, and messages that are already held
+    /// keep their previous outbox configuration. Senders resolve their timeouts, retries, and schema validation when they are opened,
+    /// so changes to those only affect senders opened afterwards, while gateways and routes are used by every channel opened
+    /// afterwards, including ones that existing senders reopen. Payload limits apply to every request received afterwards.
+    /// 
+    /// The whole configuration is applied at once with respect to [`Palantir::config`] and other calls to this,
+    /// but not with respect to the individual setters.
+    /// 
+    /// # Errors
+    /// Returns a [`SystemIdError`] if any zone, gateway, or routed system is not a valid system id, in which case nothing is changed.
+    /// 
+    /// # Panics
+    /// Panics if any of the settings' locks are poisoned, which should never happen.
+    pub fn apply_config(&self, config: Config) -> Result<(), SystemIdError> {
+
+        for (zone, gateway) in &config.gateways {
+            system_id::validate(zone)?;
+            system_id::validate(gateway)?;
+        }
+        for system in config.routes.keys() {
+            system_id::validate(system)?;
         }
+
+        let _config = self.config_lock.lock().expect("config mutex should never be poisoned");
+
+        self.set_dedup_window(config.dedup_window);
+        self.set_idempotency_ttl(config.idempotency_ttl);
+        self.set_outbox(config.outbox);
+        {
+            let mut gateways = self.gateways.write().expect("gateways lock should never be poisoned");
+            gateways.replace(config.gateways);
+            gateways.replace_routes(config.routes);
+        }
+        *self.timeouts.write().expect("timeouts lock should never be poisoned") = Timeouts {
+            default: config.request_timeout,
+            overrides: config.message_timeouts,
+        };
+        self.set_retry_policy(config.retry_policy);
+        *self.payload_limits.write().expect("payload limits lock should never be poisoned") = PayloadLimits {
+            default: config.max_payload_size,
+            overrides: config.message_max_payload_sizes,
+        };
+        self.set_schema_validation(config.schema_validation);
+        self.set_local_resolution(config.local_resolution);
+
+        Ok(())
+    }
+
+    /// # [`Palantir::stats`]
+    /// Returns a snapshot of the statistics of every registered actor and message type, keyed by the actor's id and the message type.
+    /// 
+    /// # Panics
+    /// Panics if the stats mutex is poisoned, which should never happen.
+    #[must_use]
+    pub fn stats(&self) -> HashMap<(u64, String), ActorStats> {
+        self.stats.lock().expect("stats mutex should never be poisoned")
+            .iter()
+            .map(|(key, tracker)| (key.clone(), tracker.snapshot()))
+            .collect()
+    }
+
+    /// # [`Palantir::metrics`]
+    /// Returns a snapshot of the counters and latency histogram of every registered actor and message type,
+    /// keyed by the actor's id and the message type, to see which message flows are unhealthy.
+    /// 
+    /// # Panics
+    /// Panics if the stats mutex is poisoned, which should never happen.
+    #[must_use]
+    pub fn metrics(&self) -> HashMap<(u64, String), ActorMetrics> {
+        self.stats.lock().expect("stats mutex should never be poisoned")
+            .iter()
+            .map(|(key, tracker)| (key.clone(), tracker.metrics()))
+            .collect()
+    }
+
+    /// # [`Palantir::events`]
+    /// Subscribes to this instance's [`Event`]s.
+    /// Events published before subscribing are not received, and a receiver that falls too far behind
+    /// will skip the oldest events (see [`broadcast::error::RecvError::Lagged`]).
+    #[must_use]
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
     }
 }
 
-impl<B> Palantir<B> {
+impl<B, S: Serializer> Palantir<B, S> {
     /// # [`Palantir::register`]
-    /// Registers a specific actor as being capable of communicating over the backend with a specific message type.
+    /// Registers a specific actor as being capable of communicating over the backend with a specific message type,
+    /// with the default [`RegistrationConfig`].
+    /// 
+    /// # Panics
+    /// Panics if the join set mutex is poisoned, which should never happen.
     pub async fn register<A: Handler<M>, M: IndeterminateMessage, D: Delegate + AsRef<Self>>(&self, actor: LocalRef<A, D>)
         where M::Result: Serialize + for<'de> Deserialize<'de> {
+        self.register_with_config::<A, M, D>(actor, RegistrationConfig::default()).await;
+    }
+
+    /// # [`Palantir::registration`]
+    /// Starts registering a specific actor for several message types at once, which share a single relay task.
+    /// See [`RegistrationBuilder`].
+    pub fn registration<A: Actor, D: Delegate + AsRef<Self>>(&self, actor: LocalRef<A, D>) -> RegistrationBuilder<'_, A, D, B, S> {
+        RegistrationBuilder::new(self, actor)
+    }
+
+    /// # [`Palantir::register_with_config`]
+    /// Registers a specific actor as being capable of communicating over the backend with a specific message type,
+    /// queueing and handling its requests as configured.
+    /// 
+    /// # Panics
+    /// Panics if the join set mutex or local senders lock is poisoned, which should never happen.
+    pub async fn register_with_config<A: Handler<M>, M: IndeterminateMessage, D: Delegate + AsRef<Self>>(&self, actor: LocalRef<A, D>, config: RegistrationConfig)
+        where M::Result: Serialize + for<'de> Deserialize<'de> {
 
         // Get the actor's ID, as we will need to hold it after
         // we move the actor to a separate task
         let id = actor.get_id();
 
-        // TODO: Remove this and replace with proper logging
-        println!("{} is registering actor with id {} to handle message {}", self.system_id, actor.get_id(), M::ID);
+        info!(system = %self.system_id, actor = id, message_type = M::ID, "registering actor");
 
-        // Create the request channels
-        let (request_sender, mut request_receiver) = mpsc::channel::<Request>(256);
+        let local: Arc<dyn MessageSender<M>> = Arc::new(actor.clone());
+        let sender = self.spawn_relay(id, &config);
+        let registration = self.new_registration::<A, M, M, D>(actor, sender, config.overflow, |_, message| message);
 
-        // Clone off the join set for the spawned task
-        let join_set_clone = self.join_set.clone();
+        // Add the handler to the map.
+        self.actor_handlers.write().await
+            .insert((id, M::ID.to_string()), registration);
+        self.local_senders.write().expect("local senders lock should never be poisoned")
+            .insert((id, M::ID.to_string()), Box::new(local));
+
+        // Publish the registration. Nobody listening is not an error.
+        let _ = self.events.send(Event::RegistrationAdded { actor: id, message_type: M::ID });
         
-        // Lock the join set
-        let mut join_set = self.join_set.lock().expect("join set mutex should never be poisoned");
+    }
 
-        // Spawn a task that deserializes and relays messages to the actor
-        join_set.spawn(async move {
-            // The main loop for receiving this type of message for this specific actor
-            loop {
+    /// # [`Palantir::unregister`]
+    /// Removes the given actor's registration for the message type `M`, returning whether there was one.
+    /// Requests for the actor and message type are responded to with [`ChannelError::HandlerNotFound`] from then on,
+    /// including ones that were already queued, and the registration's relay task stops.
+    /// 
+    /// # Panics
+    /// Panics if the local senders lock is poisoned, which should never happen.
+    pub async fn unregister<A: Handler<M>, M: Message + MessageID>(&self, actor: u64) -> bool {
+        unregister_actor(&self.actor_handlers, &self.local_senders, &self.events, actor, M::ID, |_| true).await
+    }
 
-                // Receive the next message.
-                let Some(next_message) = request_receiver.recv().await else {
-                    // TODO: Better logging.
-                    // This point will only ever be reached if there are no longer
-                    // any senders, which means there will never be any others.
-                    // While this should be logged, it doesn't necessarily
-                    // mean that the palantir instance is broken, just that
-                    // this type of message will never be received again.
-                    println!("Message handler {}/{} stopped recieving messages.", actor.get_id() ,M::ID);
-                    break;
-                };
+    /// # [`Palantir::unregister_pattern`]
+    /// Removes every registration for the given pattern and the message type `M`, returning whether there were any.
+    /// See [`Palantir::unregister`].
+    pub async fn unregister_pattern<M: MessageID>(&self, pattern: &str) -> bool {
+        let mut removed = Vec::new();
 
-                // Clone the actor ref
-                let actor = actor.clone();
+        self.pattern_handlers.write().await
+            .retain(|(p, m, registration)| {
+                let matches = p == pattern && m == M::ID;
+                if matches {
+                    registration.control.remove();
+                    removed.push(registration.actor);
+                }
+                !matches
+            });
 
-                // Spawn a new task handling the message
-                join_set_clone.lock().expect("join set mutex should never be poisoned")
-                    .spawn(async move {
-                        // Deserialize the message.
-                        // While the deserialization shouldn't fail, as the message types should be known ahead of time,
-                        // there does exist a possibility that two peers have different versions of the message.
-                        // As palantir doesn't yet support message schema validation (it may in the future,
-                        // and this is actually what the introspectable crate was initially created for),
-                        // we will simply ignore messages that don't deserialize properly.
-                        let Ok(message) = pot::from_slice::<M>(next_message.data()) else {
-                            return;
-                        };
-
-                        // Handle the message
-                        let Ok(res) = actor.send(message).await else {
-                            return;
-                        };
-
-                        // Serialize it. There shouldn't be any issue serializing the response, but if it doesn't
-                        // work there is not much we can do about it
-                        let Ok(response) = pot::to_vec(&res) else {
-                            return;
-                        };
-
-                        // Send the response. Again, nothing we can really do about an error here
-                        let _ = next_message.respond(response);
-                    });
+        for actor in &removed {
+            let _ = self.events.send(Event::RegistrationRemoved { actor: *actor, message_type: M::ID });
+        }
 
-            }
-        });
+        !removed.is_empty()
+    }
 
-        // Drop the join set guard so we don't hold it over the actor handlers lock's await point.
-        drop(join_set);
+    /// # [`Palantir::resume`]
+    /// Resumes the given actor's registration for the given message type after it was paused by the [`PanicPolicy`],
+    /// returning whether there was such a registration. Pattern registrations are resumed with [`Palantir::resume_pattern`].
+    pub async fn resume(&self, actor: u64, message_type: &str) -> bool {
+        self.actor_handlers.read().await
+            .get(&(actor, message_type.to_string()))
+            .map(|registration| registration.control.set_paused(false))
+            .is_some()
+    }
 
-        // Add the handler to the map.
-        self.actor_handlers.write().await
-            .insert((id, M::ID.to_string()), request_sender);
-        
+    /// # [`Palantir::resume_pattern`]
+    /// Resumes every registration for the given pattern and message type after they were paused by the [`PanicPolicy`],
+    /// returning whether there were any such registrations.
+    pub async fn resume_pattern(&self, pattern: &str, message_type: &str) -> bool {
+        let mut resumed = false;
+
+        for (_, _, registration) in self.pattern_handlers.read().await.iter()
+            .filter(|(p, m, _)| p == pattern && m == message_type) {
+            registration.control.set_paused(false);
+            resumed = true;
+        }
+
+        resumed
     }
-}
 
-impl<B: Backend> Delegate for Palantir<B> {
-    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>> 
-        where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+    /// # [`Palantir::register_pattern`]
+    /// Registers a specific actor as handling a specific message type for every named actor matching the given pattern,
+    /// in which `*` matches any run of characters (e.g. `worker-*`). The actor receives these messages wrapped in [`Routed`],
+    /// which carries the actor each message was actually sent to.
+    /// 
+    /// Requests are only routed to a pattern if no actor is registered under that exact id,
+    /// and if several patterns match, the one registered first is used.
+    /// 
+    /// Requests are queued and handled with the default [`RegistrationConfig`].
+    /// 
+    /// # Panics
+    /// Panics if the join set mutex is poisoned, which should never happen.
+    pub async fn register_pattern<A: Handler<Routed<M>>, M: IndeterminateMessage, D: Delegate + AsRef<Self>>(&self, pattern: String, actor: LocalRef<A, D>)
+        where M::Result: Serialize + for<'de> Deserialize<'de> {
+        self.register_pattern_with_config::<A, M, D>(pattern, actor, RegistrationConfig::default()).await;
+    }
+
+    /// # [`Palantir::register_pattern_with_config`]
+    /// Registers a specific actor as handling a specific message type for every named actor matching the given pattern,
+    /// like [`Palantir::register_pattern`], but queueing and handling its requests as configured.
+    /// 
+    /// # Panics
+    /// Panics if the join set mutex is poisoned, which should never happen.
+    pub async fn register_pattern_with_config<A: Handler<Routed<M>>, M: IndeterminateMessage, D: Delegate + AsRef<Self>>(&self, pattern: String, actor: LocalRef<A, D>, config: RegistrationConfig)
+        where M::Result: Serialize + for<'de> Deserialize<'de> {
+
+        let id = actor.get_id();
+
+        info!(system = %self.system_id, actor = id, message_type = M::ID, pattern = %pattern, "registering actor for pattern");
+
+        let sender = self.spawn_relay(id, &config);
+        let registration = self.new_registration::<A, Routed<M>, M, D>(actor, sender, config.overflow, |actor, message| Routed { actor, message });
+
+        self.pattern_handlers.write().await
+            .push((pattern, M::ID.to_string(), registration));
+
+        let _ = self.events.send(Event::RegistrationAdded { actor: id, message_type: M::ID });
+    }
+
+    /// # [`Palantir::spawn_relay`]
+    /// Spawns a task that takes the requests queued for the given actor and handles them with their registration's [`Handle`],
+    /// queueing and handling them as configured. Returns the sender that requests should be queued with, which may be shared
+    /// by several registrations of the actor. The task stops once every sender is dropped.
+    /// 
+    /// The task is supervised, and restarted if it panics. Requests it was relaying at the time may go unanswered.
+    /// 
+    /// # Panics
+    /// Panics if the join set mutex is poisoned, which should never happen.
+    fn spawn_relay(&self, actor: u64, config: &RegistrationConfig) -> mpsc::Sender<Queued> {
+
+        // Create the request channels. The receiver outlives the relay, so that a restarted relay keeps receiving from it.
+        let (request_sender, request_receiver) = mpsc::channel::<Queued>(config.queue_depth.max(1));
+        let request_receiver = Arc::new(tokio::sync::Mutex::new(request_receiver));
+        let slots = config.max_concurrent.map(|max| Arc::new(Semaphore::new(max)));
+
+        // Clone off the handlers' join set for the spawned task
+        let handlers = self.handlers.clone();
+        let left = self.left.subscribe();
+        let supervisor = self.supervisor.clone();
+        let events = self.events.clone();
         
-        // We can't route to actors that are on this peer, so we will return [`None`] if the foreign system id is not provided.
-        let (system, id) = match id {
-            Identifier::Foreign(id, system) => Some((system, ActorID::Numeric(id))),
-            Identifier::ForeignNamed(name, system) => Some((system, ActorID::Named(name.to_string()))),
-            _ => None,
-        }?;
+        // Spawn a task that supervises the relay.
+        // The join set guard is a temporary, so it is released at the end of this statement.
+        self.join_set.lock().expect("join set mutex should never be poisoned")
+            .spawn(async move {
+                // The relay runs in its own join set, so that it is aborted along with this task
+                let mut restarts = 0;
+                loop {
+                    let mut relay = JoinSet::new();
+                    relay.spawn(supervision::relay(actor, request_receiver.clone(), slots.clone(), handlers.clone(), left.clone()));
 
-        // Retrieve a channel to the actor
-        let channel = self.backend.open_channel::<M>(id, system, M::ID).await?;
+                    match relay.join_next().await {
+                        Some(Err(e)) if e.is_panic() => {
+                            restarts += 1;
+                            warn!(actor, restarts, "relay panicked, restarting it");
+                            let _ = events.send(Event::RelayRestarted { actor, restarts });
+                            supervisor.report(&Failure::Relay { actor, restarts });
+                        },
+                        // The relay stopped, as there will never be any more requests
+                        _ => break,
+                    }
+                }
+            });
 
-        // Wrap the channel in a palantir sender and return
-        Some(Arc::new(PalantirSender::<B, M>::new(channel)))
+        request_sender
     }
-}
 
-/// # [`PalantirSender`]
-/// Implements [`MessageSender`] for communication with [`Palantir`].
-/// This is not exposed to the public API directly, and is only ever
-/// exposed indirectly via a dyn [`MessageSender`].
-struct PalantirSender<B: Backend, M> {
-    /// The channel that is used to send the serized messages over.
-    channel: B::Channel,
-    /// Phantom data to store the message type,
-    /// which is just used for serialization.
-    _phantom: PhantomData<M>,
-}
+    /// # [`Palantir::new_registration`]
+    /// Creates a registration that deserializes requests containing `M`, wraps them into the `N` handled by the actor, and relays them to it,
+    /// queueing them with the given sender (see [`Palantir::spawn_relay`]).
+    /// 
+    /// # Panics
+    /// Panics if the stats mutex is poisoned, which should never happen.
+    fn new_registration<A: Handler<N>, N: Message, M: IndeterminateMessage, D: Delegate>(&self, actor: LocalRef<A, D>, sender: mpsc::Sender<Queued>, overflow: OverflowPolicy, wrap: fn(ActorID, M) -> N) -> Registration
+        where N::Result: Serialize, M::Result: Serialize + for<'de> Deserialize<'de> {
 
-impl<B: Backend, M: IndeterminateMessage> PalantirSender<B,M>
-    where M::Result: Serialize + for<'a> Deserialize<'a> {
+        let id = actor.get_id();
 
-    /// # [`PalantirSender::new`]
-    /// Creates a new [`PalantirSender`] wrapping the given channel.
-    pub fn new(channel: B::Channel) -> Self {
-        Self {
-            channel,
-            _phantom: PhantomData
+        // Registrations of the same actor and message type share their statistics
+        let stats = self.stats.lock().expect("stats mutex should never be poisoned")
+            .entry((id, M::ID.to_string()))
+            .or_default()
+            .clone();
+        let stats_clone = stats.clone();
+
+        let control = Arc::new(Control::default());
+        let control_clone = control.clone();
+        let panic_policy = self.panic_policy.clone();
+        let supervisor = self.supervisor.clone();
+        let events = self.events.clone();
+        let handlers = self.handlers.clone();
+        let deregistration = Arc::new(Deregistration {
+            actor_handlers: Arc::downgrade(&self.actor_handlers),
+            pattern_handlers: Arc::downgrade(&self.pattern_handlers),
+            local_senders: Arc::downgrade(&self.local_senders),
+            events: self.events.clone(),
+        });
+
+        let handle: Handle = Arc::new(move |target, next_message| {
+            // Clone the actor ref
+            let actor = actor.clone();
+            let stats = stats_clone.clone();
+            let control = control_clone.clone();
+            let panic_policy = panic_policy.clone();
+            let supervisor = supervisor.clone();
+            let events = events.clone();
+            let handlers = handlers.clone();
+            let deregistration = deregistration.clone();
+            let span = debug_span!("handle", actor = actor.get_id(), message_type = M::ID);
+
+            Box::pin(async move {
+                let start = Instant::now();
+
+                // Deserialize the message.
+                // While the deserialization shouldn't fail, as the message types should be known ahead of time,
+                // there does exist a possibility that two peers have different versions of the message.
+                // Senders can check for this ahead of time with schema validation, but if they don't,
+                // we will simply tell the requester that the message couldn't be deserialized.
+                let message = match S::deserialize::<M>(next_message.data()) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!(error = %e, "failed to deserialize request");
+                        stats.record(start.elapsed(), Outcome::DeserializationFailed);
+                        let _ = next_message.respond(Err(ChannelError::Serialization(e.to_string())));
+                        return;
+                    }
+                };
+
+                // Handle the message in its own task, so that the handler panicking can be caught.
+                // If it panics, the response sender is dropped without sending anything.
+                let id = actor.get_id();
+                let (handled, result) = tokio::sync::oneshot::channel();
+                handlers.lock().expect("join set mutex should never be poisoned")
+                    .spawn(async move {
+                        let _ = handled.send(actor.send(wrap(target, message)).await.ok());
+                    });
+
+                let res = match result.await {
+                    Ok(Some(res)) => res,
+                    Ok(None) => {
+                        stats.record(start.elapsed(), Outcome::HandlerFailed);
+                        let _ = next_message.respond(Err(ChannelError::RemoteHandler));
+                        return;
+                    },
+                    Err(_) => {
+                        stats.record(start.elapsed(), Outcome::HandlerFailed);
+                        let _ = next_message.respond(Err(ChannelError::RemoteHandler));
+
+                        let panics = stats.panicked();
+                        warn!(panics, "handler panicked");
+                        let _ = events.send(Event::HandlerPanicked { actor: id, message_type: M::ID, panics });
+                        supervisor.report(&Failure::Handler { actor: id, message_type: M::ID, panics });
+
+                        let policy = *panic_policy.read().expect("panic policy lock should never be poisoned");
+                        if control.panicked(policy, panics) {
+                            warn!(panics, "deregistering handler");
+                            deregistration.run(&control, id, M::ID).await;
+                        }
+                        return;
+                    },
+                };
+
+                // Serialize it. There shouldn't be any issue serializing the response, but if it doesn't
+                // work all we can do is let the requester know.
+                let response = S::serialize(&res)
+                    .map_err(|e| ChannelError::Serialization(e.to_string()));
+                stats.record(start.elapsed(), if response.is_ok() { Outcome::Succeeded } else { Outcome::SerializationFailed });
+
+                // Send the response. If the requester is gone there is nothing we can really do about it
+                let _ = next_message.respond(response);
+            }.instrument(span))
+        });
+
+        Registration {
+            actor: id,
+            sender,
+            handle,
+            fingerprint: schema::fingerprint::<M>(),
+            overflow,
+            stats,
+            control,
         }
     }
 }
 
-#[async_trait::async_trait]
-impl<B: Backend, M: IndeterminateMessage> MessageSender<M> for PalantirSender<B,M>
-    where M::Result: Serialize + for<'a> Deserialize<'a> {
-    
+impl<B: Backend, S: Serializer> Palantir<B, S> {
+    /// # [`Palantir::serve`]
+    /// Receives inbound requests from the backend and dispatches them to the registered actors,
+    /// until the backend stops producing requests or this instance leaves the cluster (see [`Palantir::leave_cluster`]).
+    /// This should generally be spawned as its own task.
+    pub async fn serve(&self) {
+        let mut left = self.left.subscribe();
 
-    async fn send(&self, message:M) -> Result<M::Result,Box<dyn Error> > {
-        
-        // Serialze the message
-        let message = pot::to_vec(&message)?;
+        async {
+            // Whether the backend may still report changes in its peers
+            let mut peers = true;
 
-        // Send the message
-        let response = self.channel.request(message).await.unwrap(); // # TODO: Need to redo errors again. Most likely will get rid of boxed error types, and instead use a sized type.
+            loop {
+                let next = tokio::select! {
+                    next = self.backend.incoming() => next,
+                    event = self.backend.peer_event(), if peers => {
+                        match event {
+                            Some(event) => self.peer_event(event),
+                            None => peers = false,
+                        }
+                        continue;
+                    },
+                    _ = left.wait_for(|left| *left) => break,
+                };
+
+                let Some((actor, message_type, request)) = next else {
+                    break;
+                };
 
-        // Decode the response
-        let response: M::Result = pot::from_slice(&response)?;
+                let span = debug_span!("request", actor = ?actor, message_type = %message_type);
+                self.dispatch(actor, message_type, request).instrument(span).await;
+            }
 
-        Ok(response)
+            debug!("stopped serving");
+        }.instrument(info_span!("serve", system = %self.system_id)).await;
+    }
+
+    /// # [`Palantir::peer_event`]
+    /// Publishes a change in the backend's peers.
+    fn peer_event(&self, event: PeerEvent) {
+        let event = match event {
+            PeerEvent::Connected { system } => {
+                info!(peer = %system, "peer connected");
+                Event::PeerConnected { system }
+            },
+            PeerEvent::Disconnected { system, reason } => {
+                info!(peer = %system, reason = %reason, "peer disconnected");
+                Event::PeerDisconnected { system, reason }
+            },
+        };
+
+        let _ = self.events.send(event);
     }
 
-}
\ No newline at end of file
+    /// # [`Palantir::leave_cluster`]
+    /// Gracefully takes this instance out of the cluster. New inbound requests are rejected with [`ChannelError::Leaving`],
+    /// which tells the senders on other systems to stop using their channels to this one, while the requests
+    /// that were already received, whether queued or being handled, are given until the timeout to be handled.
+    /// After that, requests that are still queued are rejected with [`ChannelError::Leaving`], the ones being handled
+    /// are dropped without a response, and [`Palantir::serve`] returns. Other tasks, such as requests being forwarded
+    /// to other systems, are left to finish on their own.
+    /// Returns whether every request was handled before the timeout.
+    /// 
+    /// # Panics
+    /// Panics if the stats or join set mutexes are poisoned, which should never happen.
+    pub async fn leave_cluster(&self, timeout: Duration) -> bool {
+        self.leaving.store(true, Ordering::Relaxed);
+
+        // Wait for the requests that were already received to drain, counting the ones still queued for the relays
+        let deadline = Instant::now() + timeout;
+        let drained = loop {
+            let in_flight = self.stats.lock().expect("stats mutex should never be poisoned")
+                .values()
+                .map(|tracker| tracker.in_flight())
+                .sum::<usize>();
+
+            if in_flight == 0 {
+                break true;
+            }
+
+            if Instant::now() >= deadline {
+                break false;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10).min(deadline.saturating_duration_since(Instant::now()))).await;
+        };
+
+        // The relays reject everything that is still queued once we've left,
+        // which they get to once the handlers still running are stopped and free their slots
+        self.left.send_replace(true);
+        self.handlers.lock().expect("join set mutex should never be poisoned")
+            .abort_all();
+
+        info!(system = %self.system_id, drained, "left the cluster");
+        drained
+    }
+
+    /// # [`Palantir::dispatch`]
+    /// Forwards an inbound request to the handler registered for the given actor and message type,
+    /// responding with [`ChannelError::HandlerNotFound`] if there is none. Requests that palantir handles itself,
+    /// such as ones to forward to other systems, are intercepted here.
+    /// 
+    /// # Panics
+    /// Panics if the join set or dedup mutexes are poisoned, which should never happen.
+    async fn dispatch(&self, actor: ActorID, message_type: String, request: Request) {
+
+        if self.leaving.load(Ordering::Relaxed) {
+            debug!("rejecting request while leaving the cluster");
+            let _ = request.respond(Err(ChannelError::Leaving));
+            return;
+        }
+
+        // Reject oversized requests before decoding anything
+        let Some(request) = self.check_size(&message_type, request) else {
+            return;
+        };
+
+        // Replay the responses to requests we've already seen
+        let Some(request) = self.deduplicate(request) else {
+            debug!("replaying the response to a duplicate request");
+            return;
+        };
+
+        // Requests to forward to other systems are relayed in their own task, as they wait on another system.
+        if message_type == Forward::ID {
+            let backend = self.backend.clone();
+            let gateways = self.gateways.clone();
+            self.join_set.lock().expect("join set mutex should never be poisoned")
+                .spawn(async move {
+                    federation::relay(backend.as_ref(), &gateways, request).await;
+                });
+            return;
+        }
+
+        // Probes are echoed back without being handled by any actor
+        if message_type == Probe::ID {
+            let data = request.data().to_vec();
+            let _ = request.respond(Ok(data));
+            return;
+        }
+
+        if message_type == Keyed::ID {
+            self.dispatch_keyed(actor, request).await;
+            return;
+        }
+
+        if message_type == Query::ID {
+            self.dispatch_query(&actor, request).await;
+            return;
+        }
+
+        if message_type == Handshake::ID {
+            self.dispatch_handshake(&actor, request).await;
+            return;
+        }
+
+        if message_type == Broadcast::ID {
+            self.dispatch_broadcast(request).await;
+            return;
+        }
+
+        if message_type == Expiring::ID {
+            self.dispatch_expiring(actor, request).await;
+            return;
+        }
+
+        self.deliver(actor, message_type, request).await;
+    }
+
+    /// # [`Palantir::deduplicate`]
+    /// Returns the request to handle in place of the given one, or [`None`] if it is a duplicate of a request that was recently
+    /// received, in which case it is responded to with that request's response once there is one (see [`Palantir::set_dedup_window`]).
+    /// 
+    /// # Panics
+    /// Panics if the join set or dedup mutexes are poisoned, which should never happen.
+    fn deduplicate(&self, request: Request) -> Option<Request> {
+        let Some(id) = request.id.clone() else {
+            return Some(request);
+        };
+
+        let mut request = {
+            let mut dedup = self.dedup.lock().expect("dedup mutex should never be poisoned");
+            let Some(dedup) = dedup.as_mut() else {
+                return Some(request);
+            };
+            dedup.begin(id.clone(), request)?
+        };
+
+        // Handle the request's data in its place, and remember its response once it arrives
+        let (inner, response) = Request::new(std::mem::take(&mut request.data));
+        let dedup = self.dedup.clone();
+        self.join_set.lock().expect("join set mutex should never be poisoned")
+            .spawn(async move {
+                let response = response.await;
+
+                // Requests that are dropped unanswered leave their duplicates unanswered too
+                if let Some(dedup) = dedup.lock().expect("dedup mutex should never be poisoned").as_mut() {
+                    match &response {
+                        Ok(response) => dedup.finish(id, response),
+                        Err(_) => dedup.abandon(&id),
+                    }
+                }
+
+                if let Ok(response) = response {
+                    let _ = request.respond(response);
+                }
+            });
+
+        Some(inner)
+    }
+
+    /// # [`Palantir::dispatch_keyed`]
+    /// Handles a request carrying an idempotency key, by delivering it to its handler only if
+    /// the key hasn't been seen recently, and responding with the cached response otherwise.
+    /// 
+    /// # Panics
+    /// Panics if the join set or idempotency mutexes are poisoned, which should never happen.
+    async fn dispatch_keyed(&self, actor: ActorID, request: Request) {
+
+        let keyed = match pot::from_slice::<Keyed>(request.data()) {
+            Ok(keyed) => keyed,
+            Err(e) => {
+                let _ = request.respond(Err(ChannelError::Serialization(e.to_string())));
+                return;
+            }
+        };
+
+        let key = (actor.clone(), keyed.message_type.clone(), keyed.key);
+
+        let Some(request) = self.idempotency.lock().expect("idempotency mutex should never be poisoned")
+            .begin(key.clone(), request) else {
+            return;
+        };
+
+        // Deliver the actual message, and cache its response once it arrives
+        let (inner, response) = Request::new(keyed.data);
+        let idempotency = self.idempotency.clone();
+        self.join_set.lock().expect("join set mutex should never be poisoned")
+            .spawn(async move {
+                // The handler always responds, so this only fails if it was torn down
+                let response = response.await.unwrap_or(Err(ChannelError::RemoteHandler));
+
+                idempotency.lock().expect("idempotency mutex should never be poisoned")
+                    .finish(key, &response);
+
+                let _ = request.respond(response);
+            });
+
+        self.deliver(actor, keyed.message_type, inner).await;
+    }
+
+    /// # [`Palantir::dispatch_expiring`]
+    /// Handles a request with a time to live, by delivering it to its handler only if it hasn't expired yet,
+    /// and responding with [`ChannelError::Expired`] otherwise.
+    async fn dispatch_expiring(&self, actor: ActorID, request: Request) {
+
+        let expiring = match pot::from_slice::<Expiring>(request.data()) {
+            Ok(expiring) => expiring,
+            Err(e) => {
+                let _ = request.respond(Err(ChannelError::Serialization(e.to_string())));
+                return;
+            }
+        };
+
+        if ttl::is_expired(expiring.deadline) {
+            let _ = request.respond(Err(ChannelError::Expired));
+            return;
+        }
+
+        // Deliver the actual message in place of the envelope
+        let request = Request { data: expiring.data, ..request };
+        self.deliver(actor, expiring.message_type, request).await;
+    }
+
+    /// # [`Palantir::dispatch_query`]
+    /// Responds to a [`Query`] with whether the given actor has a registration for the queried message type that can serve requests.
+    async fn dispatch_query(&self, actor: &ActorID, request: Request) {
+
+        let query = match pot::from_slice::<Query>(request.data()) {
+            Ok(query) => query,
+            Err(e) => {
+                let _ = request.respond(Err(ChannelError::Serialization(e.to_string())));
+                return;
+            }
+        };
+
+        let handled = self.handler(actor, &query.message_type).await
+            .is_some_and(|handler| !handler.control.is_removed() && !handler.control.is_paused());
+
+        let _ = request.respond(pot::to_vec(&handled)
+            .map_err(|e| ChannelError::Serialization(e.to_string())));
+    }
+
+    /// # [`Palantir::dispatch_handshake`]
+    /// Responds to a [`Handshake`] with the fingerprint of the given actor's registration for the requested message type,
+    /// or with [`ChannelError::HandlerNotFound`] if there is no such registration.
+    async fn dispatch_handshake(&self, actor: &ActorID, request: Request) {
+
+        let handshake = match pot::from_slice::<Handshake>(request.data()) {
+            Ok(handshake) => handshake,
+            Err(e) => {
+                let _ = request.respond(Err(ChannelError::Serialization(e.to_string())));
+                return;
+            }
+        };
+
+        let Some(handler) = self.handler(actor, &handshake.message_type).await else {
+            let _ = request.respond(Err(ChannelError::HandlerNotFound));
+            return;
+        };
+
+        let _ = request.respond(pot::to_vec(&handler.fingerprint)
+            .map_err(|e| ChannelError::Serialization(e.to_string())));
+    }
+
+    /// # [`Palantir::dispatch_broadcast`]
+    /// Delivers a [`Broadcast`] to the actor with the lowest id that is registered for its message type and can serve requests,
+    /// responding with [`ChannelError::HandlerNotFound`] if there is none.
+    async fn dispatch_broadcast(&self, request: Request) {
+
+        let broadcast = match pot::from_slice::<Broadcast>(request.data()) {
+            Ok(broadcast) => broadcast,
+            Err(e) => {
+                let _ = request.respond(Err(ChannelError::Serialization(e.to_string())));
+                return;
+            }
+        };
+
+        // Picking the lowest id means the same actor receives every broadcast
+        let actor = self.actor_handlers.read().await
+            .iter()
+            .filter(|((_, message_type), registration)| *message_type == broadcast.message_type
+                && !registration.control.is_removed() && !registration.control.is_paused())
+            .map(|((id, _), _)| *id)
+            .min();
+
+        let Some(actor) = actor else {
+            let _ = request.respond(Err(ChannelError::HandlerNotFound));
+            return;
+        };
+
+        let request = Request { data: broadcast.data, ..request };
+        self.deliver(ActorID::Numeric(actor), broadcast.message_type, request).await;
+    }
+
+    /// # [`Palantir::handler`]
+    /// Returns the registration for the given actor and message type, if there is one.
+    /// The registration is cloned, so that the lock isn't held while waiting for space in its queue.
+    async fn handler(&self, actor: &ActorID, message_type: &str) -> Option<Registration> {
+        match actor {
+            // Exact handlers are only ever registered under numeric ids
+            ActorID::Numeric(id) => self.actor_handlers.read().await
+                .get(&(*id, message_type.to_string())).cloned(),
+            // Named actors can only be served by patterns
+            ActorID::Named(name) => self.pattern_handlers.read().await
+                .iter()
+                .find(|(pattern, handled_type, _)| handled_type == message_type && pattern::matches(pattern, name))
+                .map(|(_, _, handler)| handler.clone()),
+        }
+    }
+
+    /// # [`Palantir::check_size`]
+    /// Returns the request if it is within the payload limit of the given message type,
+    /// and otherwise responds to it with [`ChannelError::TooLarge`].
+    /// 
+    /// # Panics
+    /// Panics if the payload limits lock is poisoned, which should never happen.
+    fn check_size(&self, message_type: &str, request: Request) -> Option<Request> {
+        let size = request.data().len();
+
+        match self.max_payload_size(message_type) {
+            Some(limit) if size > limit => {
+                warn!(size, limit, "rejecting oversized request");
+                let _ = request.respond(Err(ChannelError::TooLarge { size, limit }));
+                None
+            },
+            _ => Some(request),
+        }
+    }
+
+    /// # [`Palantir::deliver`]
+    /// Delivers a request to the handler registered for the given actor and message type,
+    /// responding with [`ChannelError::HandlerNotFound`] if there is none.
+    async fn deliver(&self, actor: ActorID, message_type: String, request: Request) {
+
+        // Requests unwrapped from envelopes are checked against their own message type's limit
+        let Some(mut request) = self.check_size(&message_type, request) else {
+            return;
+        };
+
+        if let ControlFlow::Break(error) = self.middleware.inbound(&actor, &message_type, &mut request) {
+            debug!(error = ?error, "middleware rejected request");
+            let _ = request.respond(Err(error));
+            return;
+        }
+
+        let Some(handler) = self.handler(&actor, &message_type).await else {
+            debug!("no handler registered");
+            let _ = request.respond(Err(ChannelError::HandlerNotFound));
+            return;
+        };
+
+        // Paused and removed registrations can't serve requests, and removed ones can be forgotten.
+        if handler.control.is_removed() {
+            self.forget_removed().await;
+        }
+        if handler.control.is_removed() || handler.control.is_paused() {
+            let _ = request.respond(Err(ChannelError::HandlerNotFound));
+            return;
+        }
+
+        // If the handler's task has stopped, treat it the same as a missing handler.
+        handler.stats.enqueued();
+        let queued = Queued {
+            target: actor,
+            request,
+            stats: handler.stats.clone(),
+            control: handler.control.clone(),
+            handle: handler.handle.clone(),
+        };
+        let rejected = match handler.overflow {
+            OverflowPolicy::Block => handler.sender.send(queued).await
+                .err().map(|mpsc::error::SendError(queued)| (queued.request, Some(ChannelError::HandlerNotFound))),
+            OverflowPolicy::Reject | OverflowPolicy::Drop => match handler.sender.try_send(queued) {
+                Ok(()) => None,
+                Err(mpsc::error::TrySendError::Full(queued)) => Some((queued.request,
+                    (handler.overflow == OverflowPolicy::Reject).then_some(ChannelError::Overloaded))),
+                Err(mpsc::error::TrySendError::Closed(queued)) => Some((queued.request, Some(ChannelError::HandlerNotFound))),
+            },
+        };
+
+        if let Some((request, error)) = rejected {
+            handler.stats.dequeued();
+            warn!(actor = handler.actor, error = ?error, "rejecting request");
+
+            // Dropped requests go unanswered
+            if let Some(error) = error {
+                let _ = request.respond(Err(error));
+            }
+        }
+    }
+
+    /// # [`Palantir::forget_removed`]
+    /// Drops every registration that was removed, which lets their relay tasks stop.
+    async fn forget_removed(&self) {
+        self.actor_handlers.write().await
+            .retain(|_, registration| !registration.control.is_removed());
+        self.pattern_handlers.write().await
+            .retain(|(_, _, registration)| !registration.control.is_removed());
+    }
+
+    /// # [`Palantir::encode`]
+    /// Serializes a message to send to actors on other systems, and passes it through the middleware.
+    fn encode<M: Serialize + MessageID>(&self, message: &M) -> Result<Vec<u8>, PalantirSendError> {
+        let mut data = S::serialize(message)
+            .map_err(|e| PalantirSendError::serialization(M::ID, e))?;
+
+        self.middleware.outbound(M::ID, &mut data);
+
+        Ok(data)
+    }
+
+    /// # [`Palantir::notify`]
+    /// Sends a message to the given actor on the given foreign system without waiting for it to be handled,
+    /// returning once it has been sent (see [`Channel::send_no_reply`](backend::Channel::send_no_reply)).
+    /// The actor's response, and any failure to handle the message, are discarded by the receiving system.
+    /// 
+    /// Unlike [`Palantir::open_sender`], this opens a new channel for every message.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError`] if the message couldn't be serialized or sent.
+    pub async fn notify<M: IndeterminateMessage>(&self, system: &str, actor: ActorID, message: M) -> Result<(), MessageSendError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let data = self.encode(&message)?;
+
+        let link = federation::open_link::<B, M>(&self.backend, &self.gateways, actor, system, M::ID).await
+            .map_err(PalantirSendError::from)?;
+
+        link.send_no_reply(data).await
+            .map_err(|e| PalantirSendError::from(e).into())
+    }
+
+    /// # [`Palantir::send_idempotent`]
+    /// Sends a message to the given actor on the given foreign system, with the given idempotency key.
+    /// If the actor's system has recently received a request with the same key for the same actor and message type,
+    /// the actor doesn't handle the message again, and the response to the earlier request is returned instead.
+    /// This is meant for retrying requests to handlers that aren't idempotent themselves.
+    /// 
+    /// Unlike [`Palantir::open_sender`], this opens a new channel for every message.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError`] if the message couldn't be serialized, delivered, or handled.
+    pub async fn send_idempotent<M: IndeterminateMessage>(&self, system: &str, actor: ActorID, key: String, message: M) -> Result<M::Result, MessageSendError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let data = self.encode(&message)?;
+
+        let data = pot::to_vec(&Keyed {
+            key,
+            message_type: M::ID.to_string(),
+            data,
+        }).map_err(|e| PalantirSendError::serialization(M::ID, e))?;
+
+        let link = federation::open_link::<B, Keyed>(&self.backend, &self.gateways, actor, system, Keyed::ID).await
+            .map_err(PalantirSendError::from)?;
+
+        let response = link.request(data).await
+            .map_err(PalantirSendError::from)?;
+
+        S::deserialize(&response)
+            .map_err(|e| PalantirSendError::deserialization(M::ID, e).into())
+    }
+
+    /// # [`Palantir::send_with_ttl`]
+    /// Sends a message to the given actor on the given foreign system, which is dropped if it can't be handled within the given time to live.
+    /// If the system is unreachable and the outbox is enabled (see [`Palantir::set_outbox`]), the message is held for at most its time to live.
+    /// The receiving system discards the message instead of handling it if it arrives after its time to live has passed.
+    /// Once its time to live has passed, this stops waiting, whether the channel is still being opened or the message is in flight.
+    /// Either way, the message expiring is reported as a [`PalantirSendError::Transport`] of [`ChannelError::Expired`].
+    /// 
+    /// Unlike [`Palantir::open_sender`], this opens a new channel for every message.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError`] if the message couldn't be serialized, delivered, or handled, or if it expired.
+    pub async fn send_with_ttl<M: IndeterminateMessage>(&self, system: &str, actor: ActorID, ttl: Duration, message: M) -> Result<M::Result, MessageSendError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let expires = Instant::now() + ttl;
+
+        let data = self.encode(&message)?;
+
+        let data = pot::to_vec(&Expiring {
+            deadline: ttl::deadline_after(ttl),
+            message_type: M::ID.to_string(),
+            data,
+        }).map_err(|e| PalantirSendError::serialization(M::ID, e))?;
+
+        // Opening the channel through a sender lets the message wait in the outbox, but only until it expires.
+        let sender = PalantirSender::<B, Expiring, S>::new(self.backend.clone(), self.gateways.clone(), self.outbox.clone(), None, system.to_string(), actor, self.events.clone());
+
+        // Once the message has expired there is no point in opening the channel or waiting for its response
+        let response = tokio::time::timeout_at(expires.into(), async {
+            let link = sender.channel(Some(expires)).await?;
+            link.request(data).await.map_err(PalantirSendError::from)
+        }).await
+            .unwrap_or(Err(PalantirSendError::from(ChannelError::Expired)))?;
+
+        S::deserialize(&response)
+            .map_err(|e| PalantirSendError::deserialization(M::ID, e).into())
+    }
+
+    /// # [`Palantir::multicast`]
+    /// Sends a message to every member of the given group concurrently, returning each member's result alongside
+    /// its system and actor, in the order they joined. A group without members results in no responses.
+    /// 
+    /// Unlike [`Palantir::open_sender`], this opens a new channel to every member for every message.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError::SerializationError`] if the message couldn't be serialized.
+    /// Failures to deliver the message to individual members are reported in their results instead.
+    pub async fn multicast<M: IndeterminateMessage>(&self, group: &str, message: M) -> Result<Vec<(String, ActorID, Result<M::Result, MessageSendError>)>, MessageSendError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let data = self.encode(&message)?;
+
+        let members = self.group_members(group);
+
+        let mut tasks = JoinSet::new();
+        for (index, (system, actor)) in members.iter().enumerate() {
+            self.spawn_request::<M>(&mut tasks, index, system.clone(), actor.clone(), data.clone());
+        }
+
+        // Collect the responses in the order the members joined
+        let mut responses = std::iter::repeat_with(|| None).take(members.len()).collect::<Vec<_>>();
+        while let Some(res) = tasks.join_next().await {
+            if let Ok((index, response)) = res {
+                responses[index] = Some(response);
+            }
+        }
+
+        Ok(members.into_iter().zip(responses)
+            .map(|((system, actor), response)| {
+                // A request task only goes missing if it panicked
+                let response = response.unwrap_or(Ok(Err(ChannelError::Closed)));
+                (system, actor, decode_response::<M, S>(response))
+            })
+            .collect())
+    }
+
+    /// # [`Palantir::broadcast`]
+    /// Sends a message to every system the backend is connected to (see [`Backend::systems`]) concurrently,
+    /// returning each system's result keyed by the system's id. Each system delivers the message to the actor registered for
+    /// its type with the lowest id, and systems with no such actor are left out of the results.
+    /// 
+    /// Unlike [`Palantir::open_sender`], this opens a new channel to every system for every message.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError::SerializationError`] if the message couldn't be serialized.
+    /// Failures to deliver the message to individual systems are reported in their results instead.
+    pub async fn broadcast<M: IndeterminateMessage>(&self, message: M) -> Result<HashMap<String, Result<M::Result, MessageSendError>>, MessageSendError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let data = self.encode(&message)?;
+
+        let data = pot::to_vec(&Broadcast {
+            message_type: M::ID.to_string(),
+            data,
+        }).map_err(|e| PalantirSendError::serialization(M::ID, e))?;
+
+        let systems = self.backend.systems().await;
+
+        let mut tasks = JoinSet::new();
+        for (index, system) in systems.iter().enumerate() {
+            self.spawn_request::<Broadcast>(&mut tasks, index, system.clone(), ActorID::Named(fanout::BROADCAST_ACTOR.to_string()), data.clone());
+        }
+
+        let mut responses = HashMap::new();
+        while let Some(res) = tasks.join_next().await {
+            // A request task only fails to join if it panicked, which leaves its system out
+            let Ok((index, response)) = res else {
+                continue;
+            };
+
+            let response = decode_response::<M, S>(response);
+            if let Err(e) = &response {
+                if matches!(PalantirSendError::of(e), Some(PalantirSendError::ActorNotFound)) {
+                    continue;
+                }
+            }
+
+            responses.insert(systems[index].clone(), response);
+        }
+
+        Ok(responses)
+    }
+
+    /// # [`Palantir::scatter_gather`]
+    /// Sends a message to every given target, as (system, actor), concurrently, and gathers their responses until
+    /// the [`Quorum`] is reached, it can no longer be reached, or the timeout passes. Requests that are still
+    /// outstanding at that point are cancelled, and reported as pending.
+    /// 
+    /// Unlike [`Palantir::open_sender`], this opens a new channel to every target for every message.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError::SerializationError`] if the message couldn't be serialized.
+    /// Failures to deliver the message to individual targets are reported in their results instead.
+    pub async fn scatter_gather<M: IndeterminateMessage>(&self, targets: Vec<(String, ActorID)>, message: M, quorum: Quorum, timeout: Duration) -> Result<Gathered<M::Result>, MessageSendError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let data = self.encode(&message)?;
+
+        let required = quorum.required(targets.len());
+
+        let mut tasks = JoinSet::new();
+        for (index, (system, actor)) in targets.iter().enumerate() {
+            self.spawn_request::<M>(&mut tasks, index, system.clone(), actor.clone(), data.clone());
+        }
+
+        let mut targets = targets.into_iter().map(Some).collect::<Vec<_>>();
+        let mut responses = Vec::new();
+        let mut successes = 0;
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        while successes < required && successes + tasks.len() >= required {
+            let Ok(Some(res)) = tokio::time::timeout_at(deadline, tasks.join_next()).await else {
+                break;
+            };
+
+            // A request task only fails to join if it panicked, in which case its target stays pending
+            let Ok((index, response)) = res else {
+                continue;
+            };
+
+            let response = decode_response::<M, S>(response);
+            if response.is_ok() {
+                successes += 1;
+            }
+
+            if let Some((system, actor)) = targets[index].take() {
+                responses.push((system, actor, response));
+            }
+        }
+
+        Ok(Gathered {
+            reached: successes >= required,
+            responses,
+            pending: targets.into_iter().flatten().collect(),
+        })
+    }
+
+    /// # [`Palantir::send_hedged`]
+    /// Sends a message to the given actor on the given foreign system, and if it hasn't been responded to after the given delay,
+    /// sends it again over a second channel. The first successful response is returned, and the other request is cancelled.
+    /// As the actor may receive the message twice, this should only be used for idempotent messages, or alongside deduplication
+    /// (see [`Palantir::send_idempotent`]).
+    /// 
+    /// Unlike [`Palantir::open_sender`], this opens a new channel for every request.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError`] if the message couldn't be serialized, or if every request sent failed,
+    /// in which case the last failure is returned.
+    pub async fn send_hedged<M: IndeterminateMessage>(&self, system: &str, actor: ActorID, delay: Duration, message: M) -> Result<M::Result, MessageSendError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let data = self.encode(&message)?;
+
+        let mut tasks = JoinSet::new();
+        self.spawn_request::<M>(&mut tasks, 0, system.to_string(), actor.clone(), data.clone());
+
+        // Only hedge if the first request is slow, not if it fails quickly
+        let mut hedged = false;
+        let mut failure = None;
+        loop {
+            let next = if hedged {
+                tasks.join_next().await
+            } else if let Ok(next) = tokio::time::timeout(delay, tasks.join_next()).await {
+                next
+            } else {
+                hedged = true;
+                self.spawn_request::<M>(&mut tasks, 1, system.to_string(), actor.clone(), data.clone());
+                continue;
+            };
+
+            // Every request failed, and a request task only fails to join if it panicked
+            let Some(res) = next else {
+                return Err(failure.unwrap_or_else(|| PalantirSendError::from(ChannelError::Closed).into()));
+            };
+
+            match res.map(|(_, response)| decode_response::<M, S>(response)) {
+                Ok(Ok(response)) => return Ok(response),
+                Ok(Err(e)) => failure = Some(e),
+                Err(_) => {},
+            }
+        }
+    }
+
+    /// # [`Palantir::spawn_request`]
+    /// Spawns a task on the given join set that opens a new channel to the given actor and sends the serialized message over it,
+    /// resolving to the given index alongside the response, which can be decoded with [`decode_response`].
+    /// This is used to send requests to many actors concurrently.
+    fn spawn_request<M: IndeterminateMessage>(&self, tasks: &mut JoinSet<(usize, RawResponse)>, index: usize, system: String, actor: ActorID, data: Vec<u8>)
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let backend = self.backend.clone();
+        let gateways = self.gateways.clone();
+
+        tasks.spawn(async move {
+            let response = async {
+                let link = federation::open_link::<B, M>(&backend, &gateways, actor, &system, M::ID).await?;
+
+                Ok(link.request(data).await)
+            }.await;
+
+            (index, response)
+        });
+    }
+
+    /// # [`Palantir::resolve`]
+    /// Opens a [`MessageSender`] to the actor referenced by the given [`RemoteRef`].
+    /// 
+    /// # Errors
+    /// Returns [`OpenChannelError::MessageNotHandled`] if the reference was created for a different message type,
+    /// and otherwise the backend's [`OpenChannelError`] if a channel to the actor could not be opened.
+    pub async fn resolve<M: IndeterminateMessage>(&self, reference: &RemoteRef<M>) -> Result<Arc<dyn MessageSender<M>>, OpenChannelError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        if !reference.is_valid() {
+            return Err(OpenChannelError::MessageNotHandled(reference.message_type().to_string()));
+        }
+
+        self.open_sender(reference.system(), reference.actor().clone()).await
+    }
+
+    /// # [`Palantir::send_journaled`]
+    /// Sends a message to the given actor on the given foreign system, recording it in the journal before it is sent,
+    /// and recording its acknowledgement once the system responds (see [`Palantir::set_journal`]).
+    /// If the journal is disabled, the message is sent without being recorded.
+    /// 
+    /// Unlike [`Palantir::open_sender`], this opens a new channel for every message.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError`] if the message couldn't be serialized, recorded, delivered, or handled.
+    /// 
+    /// # Panics
+    /// Panics if the journal lock is poisoned, which should never happen.
+    pub async fn send_journaled<M: IndeterminateMessage>(&self, system: &str, actor: ActorID, message: M) -> Result<M::Result, MessageSendError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        let data = self.encode(&message)?;
+
+        let journal = self.journal.read().expect("journal lock should never be poisoned").clone();
+
+        // The request has to be recorded before it is sent, so that it isn't lost if we crash while sending it
+        let id = journal.as_ref()
+            .map(|journal| journal.sent(system.to_string(), actor.clone(), M::ID.to_string(), data.clone()))
+            .transpose()
+            .map_err(|source| PalantirSendError::Journal { message_type: M::ID, source })?;
+
+        let response = self.send_recorded::<M>(journal.as_deref().zip(id), system, actor, M::ID, data).await;
+
+        decode_response::<M, S>(response)
+    }
+
+    /// # [`Palantir::reissue`]
+    /// Sends a journaled request (see [`Palantir::unacknowledged`]) again, returning its still serialized response,
+    /// and recording its acknowledgement once the system responds.
+    /// 
+    /// # Errors
+    /// Returns a [`MessageSendError`] if the request couldn't be delivered or handled.
+    /// 
+    /// # Panics
+    /// Panics if the journal lock is poisoned, which should never happen.
+    pub async fn reissue(&self, request: &Journaled) -> Result<Vec<u8>, MessageSendError> {
+        let journal = self.journal.read().expect("journal lock should never be poisoned").clone();
+
+        let response = self.send_recorded::<Reissued>(journal.as_deref().map(|journal| (journal, request.id)), &request.system,
+            request.actor.clone(), &request.message_type, request.data.clone()).await;
+
+        response
+            .map_err(PalantirSendError::from)?
+            .map_err(|e| PalantirSendError::from(e).into())
+    }
+
+    /// # [`Palantir::send_recorded`]
+    /// Sends a serialized request over a new channel, and records its acknowledgement in the given journal under the given id.
+    async fn send_recorded<N: Message>(&self, journal: Option<(&Journal, u64)>, system: &str, actor: ActorID, message_type: &str, data: Vec<u8>) -> RawResponse {
+        let link = federation::open_link::<B, N>(&self.backend, &self.gateways, actor, system, message_type).await?;
+        let response = link.request(data).await;
+
+        if let Some((journal, id)) = journal {
+            if journal::is_acknowledged(&response) {
+                // The request was still delivered, so failing to record that only means it might be reissued
+                let _ = journal.acknowledged(id);
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// # [`Palantir::query_actor`]
+    /// Asks the given foreign system whether the given actor exists and handles the given message type,
+    /// without sending it any messages. Registrations that are paused by the [`PanicPolicy`] don't count as handling it.
+    /// 
+    /// # Errors
+    /// Returns a [`PalantirSendError`] if the system couldn't be reached, or failed to answer the query.
+    pub async fn query_actor(&self, system: &str, actor: ActorID, message_type: &str) -> Result<bool, PalantirSendError> {
+        let query = pot::to_vec(&Query { message_type: message_type.to_string() })
+            .map_err(|e| PalantirSendError::serialization(Query::ID, e))?;
+
+        let response = match federation::open_link::<B, Query>(&self.backend, &self.gateways, actor, system, Query::ID).await {
+            Ok(link) => link.request(query).await.map_err(PalantirSendError::from),
+            Err(e) => Err(PalantirSendError::from(e)),
+        };
+
+        // Backends may already know that the actor doesn't exist, and so may systems that don't understand queries.
+        match response {
+            Ok(response) => pot::from_slice(&response)
+                .map_err(|e| PalantirSendError::deserialization(Query::ID, e)),
+            Err(PalantirSendError::ActorNotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// # [`Palantir::probe`]
+    /// Sends `count` probes with payloads of the given size to the given system one after another over a single channel,
+    /// which the system echoes back, and returns statistics about how many came back and how long they took.
+    /// 
+    /// # Errors
+    /// Returns the backend's [`OpenChannelError`] if a channel to the system could not be opened.
+    pub async fn probe(&self, system: &str, payload_size: usize, count: usize) -> Result<ProbeStats, OpenChannelError> {
+        let link = federation::open_link::<B, Probe>(&self.backend, &self.gateways, ActorID::Named(probe::PROBE_ACTOR.to_string()), system, Probe::ID).await?;
+
+        let mut latencies = Vec::with_capacity(count);
+        for i in 0..count {
+            // Vary the payload so that stale or mixed up echoes aren't counted
+            let payload = i.to_le_bytes().into_iter().cycle().take(payload_size).collect::<Vec<_>>();
+
+            let start = Instant::now();
+            if link.request(payload.clone()).await.is_ok_and(|echo| echo == payload) {
+                latencies.push(start.elapsed());
+            }
+        }
+
+        Ok(ProbeStats::from_latencies(count, payload_size, &latencies))
+    }
+
+    /// # [`Palantir::open_bounded_sender`]
+    /// Opens a [`BoundedSender`] to the given actor on the given foreign system, which allows at most `window` requests
+    /// in flight at once. See [`Palantir::open_sender`].
+    /// 
+    /// # Errors
+    /// Returns the backend's [`OpenChannelError`] if a channel to the actor could not be opened.
+    pub async fn open_bounded_sender<M: IndeterminateMessage>(&self, system: &str, actor: ActorID, window: usize) -> Result<BoundedSender<M>, OpenChannelError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+        Ok(BoundedSender::new(self.open_sender(system, actor).await?, window))
+    }
+
+    /// # [`Palantir::open_sender`]
+    /// Opens a [`MessageSender`] to the given actor on the given foreign system.
+    /// This is what [`Delegate::get_actor`] uses internally, but unlike it, this reports why the sender couldn't be opened.
+    /// Its requests time out as configured by [`Palantir::set_request_timeout`] and [`Palantir::set_message_timeout`],
+    /// and are retried as configured by [`Palantir::set_retry_policy`].
+    /// 
+    /// # Errors
+    /// Returns the backend's [`OpenChannelError`] if a channel to the actor could not be opened.
+    /// 
+    /// # Panics
+    /// Panics if the timeouts or retry policy locks are poisoned, which should never happen.
+    pub async fn open_sender<M: IndeterminateMessage>(&self, system: &str, actor: ActorID) -> Result<Arc<dyn MessageSender<M>>, OpenChannelError>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+        // Retrieve a channel to the actor
+        let link = match federation::open_link::<B, M>(&self.backend, &self.gateways, actor.clone(), system, M::ID).await {
+            Ok(link) => {
+                let _ = self.events.send(Event::ChannelOpened { system: system.to_string(), actor: actor.clone(), message_type: M::ID });
+                Some(link)
+            },
+            // If messages to unreachable systems can be held, the channel will be opened once the system is reachable
+            Err(OpenChannelError::SystemUnreachable(_)) if self.outbox.is_enabled() => None,
+            Err(e) => return Err(e),
+        };
+
+        // Wrap the channel in a palantir sender and return
+        let timeout = self.timeouts.read().expect("timeouts lock should never be poisoned").get(M::ID);
+        let retry = self.retry_policy.read().expect("retry policy lock should never be poisoned").clone();
+
+        let sender = PalantirSender::<B, M, S>::new(self.backend.clone(), self.gateways.clone(), self.outbox.clone(), link, system.to_string(), actor, self.events.clone())
+            .with_timeout(timeout)
+            .with_retry(retry)
+            .with_fingerprint(self.schema_validation.load(Ordering::Relaxed).then(schema::fingerprint::<M>).flatten())
+            .with_middleware(self.middleware.clone());
+
+        // The channel opened here skipped the sender's handshake. If its schema doesn't match,
+        // drop it, so that the sender reopens it on every send and reports the mismatch.
+        if sender.handshake().await.is_err() {
+            *sender.channel.write().await = None;
+        }
+
+        Ok(Arc::new(sender))
+    }
+}
+
+impl<B: Backend, S: Serializer> Delegate for Palantir<B, S> {
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>> 
+        where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        
+        // We can't route to actors that are on this peer, so we will return [`None`] if the foreign system id is not provided.
+        let (system, id) = match id {
+            Identifier::Foreign(id, system) => Some((system, ActorID::Numeric(id))),
+            Identifier::ForeignNamed(name, system) => Some((system, ActorID::Named(name.to_string()))),
+            _ => None,
+        }?;
+
+        // Actors registered on this system may be resolved without going through the backend
+        if let ActorID::Numeric(actor) = &id {
+            if self.local_resolution.load(Ordering::Relaxed) && system == self.system_id {
+                let local = self.local_senders.read().expect("local senders lock should never be poisoned")
+                    .get(&(*actor, M::ID.to_string()))
+                    .and_then(|local| local.downcast_ref::<Arc<dyn MessageSender<M>>>())
+                    .cloned();
+
+                if local.is_some() {
+                    return local;
+                }
+            }
+        }
+
+        // The delegate interface has no way to report why the sender couldn't be opened.
+        self.open_sender::<M>(system, id).await.ok()
+    }
+}
+
+/// # [`peer_connected`]
+/// Waits until the backend connects to a peer, or until some events were missed, in which case one may have connected.
+async fn peer_connected(events: &mut broadcast::Receiver<Event>) {
+    loop {
+        match events.recv().await {
+            Ok(Event::PeerConnected { .. }) | Err(broadcast::error::RecvError::Lagged(_)) => return,
+            Ok(_) => {},
+            Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+/// # [`LocalSenders`]
+/// The senders to locally registered actors, keyed by the actor's id and the message type.
+/// Each is an `Arc<dyn MessageSender<M>>` for its message type `M`.
+type LocalSenders = HashMap<(u64, String), Box<dyn Any + Send + Sync>>;
+
+/// # [`MAX_REOPEN_ATTEMPTS`]
+/// How many times a [`PalantirSender`] tries to reopen a broken channel to an unreachable system before giving up on a send.
+const MAX_REOPEN_ATTEMPTS: u32 = 3;
+
+/// # [`RawResponse`]
+/// The still serialized response to a request sent by [`Palantir::spawn_request`],
+/// or why the channel couldn't be opened or the request failed.
+type RawResponse = Result<Result<Vec<u8>, ChannelError>, OpenChannelError>;
+
+/// # [`decode_response`]
+/// Decodes a [`RawResponse`] to a message of type `M`.
+fn decode_response<M: IndeterminateMessage, S: Serializer>(response: RawResponse) -> Result<M::Result, MessageSendError>
+    where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+    let response = response
+        .map_err(PalantirSendError::from)?
+        .map_err(PalantirSendError::from)?;
+
+    S::deserialize(&response)
+        .map_err(|e| PalantirSendError::deserialization(M::ID, e).into())
+}
+
+/// # [`PalantirSender`]
+/// Implements [`MessageSender`] for communication with [`Palantir`].
+/// This is not exposed to the public API directly, and is only ever
+/// exposed indirectly via a dyn [`MessageSender`].
+struct PalantirSender<B: Backend, M, S> {
+    /// The backend, which is used to reopen the channel if it breaks.
+    backend: Arc<B>,
+    /// The gateways used to route the channel when reopening it.
+    gateways: Arc<std::sync::RwLock<Gateways>>,
+    /// The outbox that holds messages while the system is unreachable
+    outbox: Arc<Outbox>,
+    /// The channel that is used to send the serized messages over.
+    /// This is [`None`] if the channel broke, and will be reopened on the next send.
+    channel: RwLock<Option<Arc<Link<B::Channel>>>>,
+    /// The system the channel is connected to
+    system: String,
+    /// The actor the channel is connected to
+    actor: ActorID,
+    /// The event bus that failed requests are reported on
+    events: broadcast::Sender<Event>,
+    /// How long to wait for a response, or [`None`] to wait forever
+    timeout: Option<Duration>,
+    /// How failed requests are retried, or [`None`] to not retry them
+    retry: Option<RetryPolicy>,
+    /// The fingerprint of the message type to check the actor's against once the channel is opened, or [`None`] to not check it
+    fingerprint: Option<u64>,
+    /// The middleware messages pass through before they are sent
+    middleware: Arc<Chain>,
+    /// Phantom data to store the message type and serializer,
+    /// which are just used for serialization.
+    _phantom: PhantomData<(M, S)>,
+}
+
+impl<B: Backend, M: IndeterminateMessage, S: Serializer> PalantirSender<B, M, S>
+    where M::Result: Serialize + for<'a> Deserialize<'a> {
+
+    /// # [`PalantirSender::new`]
+    /// Creates a new [`PalantirSender`] wrapping the given channel to the given actor on the given system.
+    /// If no channel is given, it is opened on the first send.
+    pub fn new(backend: Arc<B>, gateways: Arc<std::sync::RwLock<Gateways>>, outbox: Arc<Outbox>, channel: Option<Link<B::Channel>>, system: String, actor: ActorID, events: broadcast::Sender<Event>) -> Self {
+        Self {
+            backend,
+            gateways,
+            outbox,
+            channel: RwLock::new(channel.map(Arc::new)),
+            system,
+            actor,
+            events,
+            timeout: None,
+            retry: None,
+            fingerprint: None,
+            middleware: Arc::default(),
+            _phantom: PhantomData
+        }
+    }
+
+    /// # [`PalantirSender::with_timeout`]
+    /// Makes the sender fail requests with a [`SendTimeout`] if they aren't responded to within the given time.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// # [`PalantirSender::with_retry`]
+    /// Makes the sender retry failed requests as configured by the given policy.
+    pub fn with_retry(mut self, retry: Option<RetryPolicy>) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// # [`PalantirSender::with_fingerprint`]
+    /// Makes the sender check that the actor's registration has the given fingerprint whenever it opens a channel.
+    pub fn with_fingerprint(mut self, fingerprint: Option<u64>) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    /// # [`PalantirSender::with_middleware`]
+    /// Makes the sender pass messages through the given middleware before sending them.
+    pub fn with_middleware(mut self, middleware: Arc<Chain>) -> Self {
+        self.middleware = middleware;
+        self
+    }
+
+    /// # [`PalantirSender::handshake`]
+    /// Checks that the actor's registration has the same fingerprint as this sender, if it has one.
+    /// Only a mismatch is an error, so handshakes with systems that don't validate schemas, or that fail, are ignored.
+    async fn handshake(&self) -> Result<(), PalantirSendError> {
+        let Some(local) = self.fingerprint else {
+            return Ok(());
+        };
+
+        let Ok(handshake) = pot::to_vec(&Handshake { message_type: M::ID.to_string() }) else {
+            return Ok(());
+        };
+
+        let Ok(link) = federation::open_link::<B, Handshake>(&self.backend, &self.gateways, self.actor.clone(), &self.system, Handshake::ID).await else {
+            return Ok(());
+        };
+
+        let remote = link.request(handshake).await.ok()
+            .and_then(|response| pot::from_slice::<Option<u64>>(&response).ok())
+            .flatten();
+
+        match remote {
+            Some(remote) if remote != local => Err(PalantirSendError::SchemaMismatch { message_type: M::ID, local, remote }),
+            _ => Ok(()),
+        }
+    }
+
+    /// # [`PalantirSender::open`]
+    /// Opens a new channel via the backend, attempting up to [`MAX_REOPEN_ATTEMPTS`] times while the system is unreachable,
+    /// and backing off between attempts as configured by the sender's [`RetryPolicy`], or the default one if it has none.
+    async fn open(&self) -> Result<Link<B::Channel>, OpenChannelError> {
+        let mut attempts = 1;
+        loop {
+            match federation::open_link::<B, M>(&self.backend, &self.gateways, self.actor.clone(), &self.system, M::ID).await {
+                // Only an unreachable system might fix itself by trying again
+                Err(OpenChannelError::SystemUnreachable(_)) if attempts < MAX_REOPEN_ATTEMPTS => {
+                    tokio::time::sleep(self.retry.clone().unwrap_or_default().backoff(attempts)).await;
+                    attempts += 1;
+                },
+                res => return res,
+            }
+        }
+    }
+
+    /// # [`PalantirSender::channel`]
+    /// Retrieves the current channel, reopening it via the backend if it broke.
+    /// If the system is unreachable and the outbox is enabled, this waits for it to become reachable again,
+    /// trying again whenever a peer connects or the outbox's retry interval passes,
+    /// but fails with [`ChannelError::Expired`] if the given deadline passes first.
+    async fn channel(&self, deadline: Option<Instant>) -> Result<Arc<Link<B::Channel>>, PalantirSendError> {
+
+        // The message's place in the outbox, once it has one, and when it got it.
+        let mut held = None;
+
+        // Peer connections since the channel was first reopened, which may make the system reachable
+        let mut connected = None;
+
+        loop {
+            if let Some(channel) = self.channel.read().await.as_ref() {
+                return Ok(channel.clone());
+            }
+
+            let mut current = self.channel.write().await;
+
+            // Another send may have reopened the channel while we were waiting for the lock
+            if let Some(channel) = current.as_ref() {
+                return Ok(channel.clone());
+            }
+
+            // Subscribe before opening, so that a peer connecting right after a failed open isn't missed
+            if connected.is_none() && self.outbox.is_enabled() {
+                connected = Some(self.events.subscribe());
+            }
+
+            let error = match self.open().await {
+                Ok(channel) => {
+                    // A mismatched channel isn't kept, so that every send reports the mismatch
+                    self.handshake().await?;
+
+                    let channel = Arc::new(channel);
+                    *current = Some(channel.clone());
+                    debug!("opened channel");
+                    let _ = self.events.send(Event::ChannelOpened { system: self.system.clone(), actor: self.actor.clone(), message_type: M::ID });
+                    return Ok(channel);
+                },
+                Err(e) => e,
+            };
+
+            // Don't hold up other sends while this one waits in the outbox.
+            drop(current);
+
+            if matches!(error, OpenChannelError::SystemUnreachable(_)) && held.is_none() {
+                held = self.outbox.hold(&self.system).map(|held| (held, Instant::now()));
+            }
+
+            let Some(((_, config), _)) = held.as_ref().filter(|((_, config), since)| since.elapsed() < config.ttl) else {
+                return Err(PalantirSendError::from(error));
+            };
+
+            // Wait to try again, but not past the deadline
+            let remaining = deadline.map_or(config.retry_interval, |deadline| deadline.saturating_duration_since(Instant::now()));
+            if remaining.is_zero() {
+                return Err(PalantirSendError::from(ChannelError::Expired));
+            }
+            let retry = tokio::time::sleep(remaining.min(config.retry_interval));
+            match connected.as_mut() {
+                Some(connected) => tokio::select! {
+                    () = retry => {},
+                    () = peer_connected(connected) => {},
+                },
+                None => retry.await,
+            }
+        }
+    }
+
+    /// # [`PalantirSender::invalidate`]
+    /// Marks the given channel as broken, so that the next send reopens it.
+    /// Does nothing if the channel was already replaced.
+    async fn invalidate(&self, channel: &Arc<Link<B::Channel>>) {
+        let mut current = self.channel.write().await;
+
+        if current.as_ref().is_some_and(|c| Arc::ptr_eq(c, channel)) {
+            *current = None;
+        }
+    }
+
+    /// # [`PalantirSender::channel_failed`]
+    /// Converts an error from the given channel into a [`PalantirSendError`],
+    /// invalidating the channel if it won't carry any more requests.
+    async fn channel_failed(&self, channel: &Arc<Link<B::Channel>>, error: ChannelError) -> PalantirSendError {
+
+        if matches!(error, ChannelError::Closed | ChannelError::PeerDisconnected) {
+            self.invalidate(channel).await;
+        }
+
+        // A system that is leaving won't accept anything else over this channel
+        if matches!(error, ChannelError::Leaving) {
+            self.invalidate(channel).await;
+            let _ = self.events.send(Event::SystemLeaving { system: self.system.clone() });
+        }
+
+        PalantirSendError::from(error)
+    }
+
+    /// # [`PalantirSender::request`]
+    /// Serializes the message, sends it over the channel, and deserializes the response,
+    /// retrying as configured by the sender's [`RetryPolicy`].
+    async fn request(&self, message: M) -> Result<M::Result, MessageSendError> {
+        
+        // Serialze the message
+        let mut message = S::serialize(&message)
+            .map_err(|e| PalantirSendError::serialization(M::ID, e))?;
+        self.middleware.outbound(M::ID, &mut message);
+
+        // Every attempt shares an id, so that the remote system can deduplicate retries
+        let id = dedup::next_id();
+
+        let mut attempt = 1;
+        loop {
+            let (error, failure) = match self.attempt(message.clone(), id).await {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
+
+            let Some(retry) = self.retry.as_ref().filter(|retry| retry.retries(attempt, failure)) else {
+                return Err(error.into());
+            };
+
+            let backoff = retry.backoff(attempt);
+            debug!(attempt, error = %error, backoff = ?backoff, "retrying request");
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+
+    /// # [`PalantirSender::attempt`]
+    /// Sends the serialized message over the channel once, and deserializes the response. If this fails, the error is returned
+    /// alongside which failures it is retried on, or [`None`] if it should never be retried.
+    /// If the peer disconnects and the outbox is enabled, the message is held in the outbox like any other message to an
+    /// unreachable system, and sent again once the channel is reopened.
+    async fn attempt(&self, message: Vec<u8>, id: u64) -> Result<M::Result, (PalantirSendError, Option<RetryOn>)> {
+
+        // When the peer first disconnected during this attempt
+        let mut disconnected = None::<Instant>;
+
+        loop {
+            // Nothing was sent if the channel couldn't be opened, but only an unreachable system might become reachable
+            let channel = self.channel(None).await.map_err(|e| {
+                let failure = matches!(e, PalantirSendError::Open(OpenChannelError::SystemUnreachable(_))).then_some(RetryOn::Safe);
+                (e, failure)
+            })?;
+
+            // Send the message, giving up after the timeout. Messages without results wait for a response as well,
+            // so that remote failures to handle them are reported. Fire and forget messages are sent with [`Palantir::notify`].
+            // The channel isn't invalidated on timeout, as only this request is known to be slow.
+            let response = match self.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, channel.request_with_id(message.clone(), id)).await
+                    .map_err(|_| (PalantirSendError::Timeout(SendTimeout {
+                        system: self.system.clone(),
+                        actor: self.actor.clone(),
+                        message_type: M::ID,
+                        timeout,
+                    }), Some(RetryOn::Transient)))?,
+                None => channel.request_with_id(message.clone(), id).await,
+            };
+
+            // Decode the response
+            let error = match response {
+                Ok(response) => return S::deserialize(&response)
+                    .map_err(|e| (PalantirSendError::deserialization(M::ID, e), None)),
+                Err(e) => e,
+            };
+
+            let failure = Self::classify(&channel, &error);
+            let disconnect = matches!(error, ChannelError::Closed | ChannelError::PeerDisconnected);
+            let error = self.channel_failed(&channel, error).await;
+
+            // Hold the message until the channel is reopened, for no longer than the outbox would hold it
+            let since = *disconnected.get_or_insert_with(Instant::now);
+            if !disconnect || self.outbox.config().is_none_or(|config| since.elapsed() >= config.ttl) {
+                return Err((error, failure));
+            }
+            debug!(error = %error, "peer disconnected, holding message");
+        }
+    }
+
+    /// # [`PalantirSender::classify`]
+    /// Returns which failures an error from the given channel is retried on, or [`None`] if it should never be retried.
+    fn classify(channel: &Link<B::Channel>, error: &ChannelError) -> Option<RetryOn> {
+        if channel.is_retryable(error) {
+            Some(RetryOn::Safe)
+        } else if matches!(error, ChannelError::Closed | ChannelError::PeerDisconnected | ChannelError::Timeout { .. }) {
+            Some(RetryOn::Transient)
+        } else {
+            None
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: Backend, M: IndeterminateMessage, S: Serializer> MessageSender<M> for PalantirSender<B, M, S>
+    where M::Result: Serialize + for<'a> Deserialize<'a> {
+    
+
+    async fn send(&self, message:M) -> Result<M::Result, MessageSendError> {
+        
+        let span = debug_span!("send", system = %self.system, actor = ?self.actor, message_type = M::ID);
+        let res = self.request(message).instrument(span).await;
+
+        // Report failures on the event bus
+        if let Err(e) = &res {
+            debug!(system = %self.system, actor = ?self.actor, message_type = M::ID, error = %e, "request failed");
+            let _ = self.events.send(Event::RequestFailed {
+                system: self.system.clone(),
+                actor: self.actor.clone(),
+                message_type: M::ID,
+                reason: e.to_string(),
+            });
+        }
+
+        res
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use fluxion::{actor, message, ActorContext, Fluxion};
+
+    use crate::{backend::memory::{MemoryBackend, MemoryNetwork}, registration::RegistrationConfig, testkit::two_systems};
+    use super::*;
+
+    /// Counts the requests it handles, and answers the first one after a delay
+    #[actor]
+    struct Replica {
+        handled: AtomicUsize,
+        delay: Duration,
+    }
+
+    #[message(usize)]
+    #[derive(Serialize, Deserialize)]
+    struct Read;
+
+    impl Handler<Read> for Replica {
+        async fn handle_message<D: Delegate>(&self, _message: Read, _context: &ActorContext<D>) -> usize {
+            let handled = self.handled.fetch_add(1, Ordering::SeqCst);
+            if handled == 0 {
+                tokio::time::sleep(self.delay).await;
+            }
+            handled
+        }
+    }
+
+    /// Starts a replica on `b` that answers its first request after the given delay
+    async fn replica(b: &Fluxion<Palantir<MemoryBackend>>, delay: Duration) -> u64 {
+        let id = b.add(Replica { handled: AtomicUsize::new(0), delay }).await.unwrap();
+        b.get_delegate().register::<Replica, Read, _>(b.get_local::<Replica>(id).await.unwrap()).await;
+        id
+    }
+
+    #[actor]
+    struct Sleeper;
+
+    /// Sleeps for the given number of milliseconds, and returns them
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Sleep(u64);
+
+    impl Handler<Sleep> for Sleeper {
+        async fn handle_message<D: Delegate>(&self, message: Sleep, _context: &ActorContext<D>) -> u64 {
+            tokio::time::sleep(Duration::from_millis(message.0)).await;
+            message.0
+        }
+    }
+
+    impl Handler<Routed<Sleep>> for Sleeper {
+        async fn handle_message<D: Delegate>(&self, message: Routed<Sleep>, _context: &ActorContext<D>) -> u64 {
+            message.message.0
+        }
+    }
+
+    /// Starts a sleeper on `b` that handles one request at a time
+    async fn sleeper(b: &Fluxion<Palantir<MemoryBackend>>) -> u64 {
+        let id = b.add(Sleeper).await.unwrap();
+        let config = RegistrationConfig { max_concurrent: Some(1), ..Default::default() };
+        b.get_delegate().register_with_config::<Sleeper, Sleep, _>(b.get_local::<Sleeper>(id).await.unwrap(), config).await;
+        id
+    }
+
+    /// Waits until the given actor's requests are queued and being handled as expected
+    async fn wait_stats(system: &Fluxion<Palantir<MemoryBackend>>, actor: u64, handling: usize, queue_depth: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let stats = system.get_delegate().stats();
+                let stats = &stats[&(actor, Sleep::ID.to_string())];
+                if stats.handling == handling && stats.queue_depth == queue_depth {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).await.expect("requests should be queued and handled");
+    }
+
+    fn is_leaving(error: &MessageSendError) -> bool {
+        matches!(PalantirSendError::of(error), Some(PalantirSendError::Transport(ChannelError::Leaving)))
+    }
+
+    /// Sends a request that is queued behind another that takes the given time, then leaves the cluster with the given timeout,
+    /// sending another request while leaving. Returns whether leaving drained every request, and the three responses,
+    /// with their errors replaced by whether they were [`ChannelError::Leaving`].
+    async fn send_behind(a: &Fluxion<Palantir<MemoryBackend>>, b: &Fluxion<Palantir<MemoryBackend>>, first: u64, leave_timeout: Duration) -> (bool, [Result<u64, bool>; 3]) {
+        let id = sleeper(b).await;
+
+        let send = |millis| {
+            let a = a.clone();
+            tokio::spawn(async move {
+                let sender = a.get::<Sleeper, Sleep>(Identifier::Foreign(id, "b")).await.unwrap();
+                sender.send(Sleep(millis)).await.map_err(|e| is_leaving(&e))
+            })
+        };
+
+        let first = send(first);
+        wait_stats(b, id, 1, 0).await;
+        let second = send(1);
+        wait_stats(b, id, 1, 1).await;
+
+        let leaving = tokio::spawn({
+            let b = b.clone();
+            async move { b.get_delegate().leave_cluster(leave_timeout).await }
+        });
+        while !b.get_delegate().leaving.load(Ordering::Relaxed) {
+            tokio::task::yield_now().await;
+        }
+        let late = send(1).await.unwrap();
+
+        (leaving.await.unwrap(), [first.await.unwrap(), second.await.unwrap(), late])
+    }
+
+    #[tokio::test]
+    async fn leave_after_draining() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        // The queued request is handled once the one ahead of it is, but new ones aren't accepted
+        let (drained, [first, second, late]) = send_behind(&a, &b, 100, Duration::from_secs(5)).await;
+        assert!(drained);
+        assert_eq!(first, Ok(100));
+        assert_eq!(second, Ok(1));
+        assert_eq!(late, Err(true));
+    }
+
+    #[tokio::test]
+    async fn leave_rejects_queued() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        // The request being handled is dropped, while the one queued behind it is rejected
+        let start = Instant::now();
+        let (drained, [first, second, late]) = send_behind(&a, &b, 5000, Duration::from_millis(50)).await;
+        assert!(!drained);
+        assert_eq!(first, Err(false));
+        assert_eq!(second, Err(true));
+        assert_eq!(late, Err(true));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn unregister() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let mut events = b.get_delegate().events();
+        let id = sleeper(&b).await;
+
+        let sender = a.get::<Sleeper, Sleep>(Identifier::Foreign(id, "b")).await.unwrap();
+        assert_eq!(sender.send(Sleep(1)).await.unwrap(), 1);
+
+        // A request queued while the registration is removed is rejected, while the one being handled finishes
+        let slow = tokio::spawn({ let sender = sender.clone(); async move { sender.send(Sleep(100)).await.ok() } });
+        wait_stats(&b, id, 1, 0).await;
+        let queued = tokio::spawn({ let sender = sender.clone(); async move { sender.send(Sleep(1)).await.map_err(|e| matches!(PalantirSendError::of(&e), Some(PalantirSendError::ActorNotFound))) } });
+        wait_stats(&b, id, 1, 1).await;
+
+        assert!(b.get_delegate().unregister::<Sleeper, Sleep>(id).await);
+        assert_eq!(slow.await.unwrap(), Some(100));
+        assert_eq!(queued.await.unwrap(), Err(true));
+
+        // Later requests find no registration
+        let error = sender.send(Sleep(1)).await.unwrap_err();
+        assert!(matches!(PalantirSendError::of(&error), Some(PalantirSendError::ActorNotFound)));
+        assert!(!b.get_delegate().unregister::<Sleeper, Sleep>(id).await);
+
+        let mut removed = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::RegistrationRemoved { actor, message_type } = event {
+                removed.push((actor, message_type));
+            }
+        }
+        assert_eq!(removed, [(id, Sleep::ID)]);
+    }
+
+    #[tokio::test]
+    async fn unregister_pattern() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Sleeper).await.unwrap();
+        b.get_delegate().register_pattern::<Sleeper, Sleep, _>("sleeper-*".to_string(), b.get_local::<Sleeper>(id).await.unwrap()).await;
+
+        let sender = a.get_delegate().open_sender::<Sleep>("b", ActorID::Named("sleeper-1".to_string())).await.unwrap();
+        assert_eq!(sender.send(Sleep(1)).await.unwrap(), 1);
+
+        assert!(b.get_delegate().unregister_pattern::<Sleep>("sleeper-*").await);
+        let error = sender.send(Sleep(1)).await.unwrap_err();
+        assert!(matches!(PalantirSendError::of(&error), Some(PalantirSendError::ActorNotFound)));
+        assert!(!b.get_delegate().unregister_pattern::<Sleep>("sleeper-*").await);
+    }
+
+    #[tokio::test]
+    async fn notify() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = sleeper(&b).await;
+
+        // Notifying doesn't wait for the message to be handled
+        let start = Instant::now();
+        a.get_delegate().notify("b", ActorID::Numeric(id), Sleep(200)).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(150));
+
+        wait_stats(&b, id, 1, 0).await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while b.get_delegate().stats()[&(id, Sleep::ID.to_string())].handled == 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).await.expect("the message should be handled");
+
+        assert!(a.get_delegate().notify("c", ActorID::Numeric(id), Sleep(1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn local_resolution() {
+        let (_a, b, _guard) = two_systems("a", "b");
+        let id = sleeper(&b).await;
+        let handled = || b.get_delegate().stats()[&(id, Sleep::ID.to_string())].handled;
+
+        // Without local resolution, requests to this system go through the backend and the registration
+        let sender = b.get::<Sleeper, Sleep>(Identifier::Foreign(id, "b")).await.unwrap();
+        assert_eq!(sender.send(Sleep(1)).await.unwrap(), 1);
+        assert_eq!(handled(), 1);
+
+        // With it, they go to the actor directly
+        b.get_delegate().set_local_resolution(true);
+        let sender = b.get::<Sleeper, Sleep>(Identifier::Foreign(id, "b")).await.unwrap();
+        assert_eq!(sender.send(Sleep(1)).await.unwrap(), 1);
+        assert_eq!(handled(), 1);
+
+        // Unless the actor isn't registered
+        assert!(b.get_delegate().unregister::<Sleeper, Sleep>(id).await);
+        let sender = b.get::<Sleeper, Sleep>(Identifier::Foreign(id, "b")).await.unwrap();
+        let error = sender.send(Sleep(1)).await.unwrap_err();
+        assert!(matches!(PalantirSendError::of(&error), Some(PalantirSendError::ActorNotFound)));
+    }
+
+    /// Waits for the next event about a peer
+    async fn next_peer_event(events: &mut broadcast::Receiver<Event>) -> Event {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let event @ (Event::PeerConnected { .. } | Event::PeerDisconnected { .. }) = events.recv().await.unwrap() {
+                    break event;
+                }
+            }
+        }).await.expect("a peer event should be published")
+    }
+
+    #[tokio::test]
+    async fn peer_events() {
+        let network = MemoryNetwork::new();
+        let a = Fluxion::new("a", Palantir::new("a".to_string(), network.backend("a")).unwrap());
+        let mut events = a.get_delegate().events();
+        let serving = tokio::spawn({
+            let a = a.clone();
+            async move { a.get_delegate().serve().await }
+        });
+
+        // Systems joining and leaving the network are published
+        let c = network.backend("c");
+        let event = next_peer_event(&mut events).await;
+        assert!(matches!(event, Event::PeerConnected { system } if system == "c"));
+
+        drop(c);
+        let event = next_peer_event(&mut events).await;
+        assert!(matches!(event, Event::PeerDisconnected { system, .. } if system == "c"));
+
+        serving.abort();
+        let _ = serving.await;
+    }
+
+    #[tokio::test]
+    async fn hedged_after_delay() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = replica(&b, Duration::from_secs(5)).await;
+
+        // The hedged request is answered first
+        let start = Instant::now();
+        let response = a.get_delegate().send_hedged("b", ActorID::Numeric(id), Duration::from_millis(50), Read).await.unwrap();
+        assert_eq!(response, 1);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn not_hedged_when_fast() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = replica(&b, Duration::from_millis(1)).await;
+
+        let response = a.get_delegate().send_hedged("b", ActorID::Numeric(id), Duration::from_secs(1), Read).await.unwrap();
+        assert_eq!(response, 0);
+
+        // Only a single request reached the actor
+        let local = b.get_local::<Replica>(id).await.unwrap();
+        assert_eq!(local.send(Read).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,112 @@
+//! # Limit
+//! Inbound requests are buffered in full before they are deserialized, so a peer could exhaust memory by sending enormous ones.
+//! [`Palantir::set_max_payload_size`](crate::Palantir::set_max_payload_size) limits the size of every inbound request, and
+//! [`Palantir::set_message_max_payload_size`](crate::Palantir::set_message_max_payload_size) overrides that for a single message type.
+//! Requests over the limit are responded to with [`ChannelError::TooLarge`](crate::backend::ChannelError::TooLarge) without being decoded.
+//! 
+//! Palantir can only check requests once the backend has received them, so backends that frame requests themselves should also reject
+//! frames whose length exceeds [`Palantir::max_payload_size`](crate::Palantir::max_payload_size) before reading them.
+
+use std::collections::HashMap;
+
+
+
+/// # [`PayloadLimits`]
+/// The maximum inbound payload sizes of a palantir instance, in bytes.
+#[derive(Default)]
+pub(crate) struct PayloadLimits {
+    /// The limit of message types without an override, or [`None`] if they are unlimited
+    pub default: Option<usize>,
+    /// The limits of individual message types
+    pub overrides: HashMap<String, usize>,
+}
+
+impl PayloadLimits {
+    /// # [`PayloadLimits::get`]
+    /// Returns the limit of the given message type, or [`None`] if it is unlimited.
+    pub fn get(&self, message_type: &str) -> Option<usize> {
+        self.overrides.get(message_type).copied().or(self.default)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use fluxion::{actor, message, ActorContext, Delegate, Fluxion, Handler, Identifier, MessageID, MessageSendError};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{backend::{memory::MemoryBackend, ChannelError}, serializer::{Pot, Serializer}, testkit::two_systems, ActorID, Palantir, PalantirSendError};
+    use super::*;
+
+    #[actor]
+    struct Sink;
+
+    #[message(usize)]
+    #[derive(Serialize, Deserialize)]
+    struct Blob(Vec<u8>);
+
+    impl Handler<Blob> for Sink {
+        async fn handle_message<D: Delegate>(&self, message: Blob, _context: &ActorContext<D>) -> usize {
+            message.0.len()
+        }
+    }
+
+    async fn sink(b: &Fluxion<Palantir<MemoryBackend>>) -> u64 {
+        let id = b.add(Sink).await.unwrap();
+        b.get_delegate().register::<Sink, Blob, _>(b.get_local::<Sink>(id).await.unwrap()).await;
+        id
+    }
+
+    /// Returns the size and limit of the oversized request, if that is why it failed
+    fn too_large(error: &MessageSendError) -> Option<(usize, usize)> {
+        match PalantirSendError::of(error) {
+            Some(PalantirSendError::Transport(ChannelError::TooLarge { size, limit })) => Some((*size, *limit)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn overrides() {
+        let limits = PayloadLimits {
+            default: Some(1024),
+            overrides: HashMap::from([(Blob::ID.to_string(), 16)]),
+        };
+
+        assert_eq!(limits.get(Blob::ID), Some(16));
+        assert_eq!(limits.get("other"), Some(1024));
+        assert_eq!(PayloadLimits::default().get(Blob::ID), None);
+    }
+
+    #[tokio::test]
+    async fn oversized() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = sink(&b).await;
+        b.get_delegate().set_max_payload_size(Some(64));
+
+        let sender = a.get::<Sink, Blob>(Identifier::Foreign(id, "b")).await.unwrap();
+        assert_eq!(sender.send(Blob(vec![0; 16])).await.unwrap(), 16);
+
+        // The sender learns the size it sent and the limit it exceeded, and the actor never sees the request
+        let size = Pot::serialize(&Blob(vec![0; 128])).unwrap().len();
+        let error = sender.send(Blob(vec![0; 128])).await.unwrap_err();
+        assert_eq!(too_large(&error), Some((size, 64)));
+        assert_eq!(b.get_delegate().stats()[&(id, Blob::ID.to_string())].handled, 1);
+    }
+
+    #[tokio::test]
+    async fn oversized_in_envelope() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = sink(&b).await;
+        b.get_delegate().set_message_max_payload_size::<Blob>(Some(64));
+
+        // The message is checked against its own limit once it is unwrapped
+        let error = a.get_delegate().send_with_ttl("b", ActorID::Numeric(id), Duration::from_secs(5), Blob(vec![0; 128])).await.unwrap_err();
+        assert!(too_large(&error).is_some_and(|(_, limit)| limit == 64));
+
+        let response = a.get_delegate().send_with_ttl("b", ActorID::Numeric(id), Duration::from_secs(5), Blob(vec![0; 16])).await.unwrap();
+        assert_eq!(response, 16);
+    }
+}
@@ -0,0 +1,160 @@
+//! # Middleware
+//! [`Middleware`] installed with [`Palantir::add_middleware`](crate::Palantir::add_middleware) sees every message the instance
+//! sends to and receives for actors, which makes it suited to cross-cutting concerns such as auth tokens, compression, or audit logging.
+//! Each hook is given the message type, so middleware can also apply to specific message types only.
+//! 
+//! Outbound messages pass through middleware in the order it was added, and inbound requests in the reverse order,
+//! so that middleware added later wraps the messages of middleware added earlier. Messages to actors that are resolved locally
+//! (see [`Palantir::set_local_resolution`](crate::Palantir::set_local_resolution)) are never serialized, and so bypass middleware.
+
+use std::{ops::ControlFlow, sync::{Arc, PoisonError, RwLock}};
+
+use crate::{backend::ChannelError, ActorID, Request};
+
+
+
+/// # [`Middleware`]
+/// Hooks that are run on every message a [`Palantir`](crate::Palantir) instance sends and receives. Both hooks do nothing by default.
+pub trait Middleware: Send + Sync + 'static {
+    /// # [`Middleware::on_inbound`]
+    /// Called with every request received for an actor on this system before it is handled, including requests for actors that
+    /// aren't registered. The request's data may be modified with [`Request::data_mut`].
+    /// Returning [`ControlFlow::Break`] responds to the request with the given error instead, and skips any remaining middleware.
+    fn on_inbound(&self, _actor: &ActorID, _message_type: &str, _request: &mut Request) -> ControlFlow<ChannelError> {
+        ControlFlow::Continue(())
+    }
+
+    /// # [`Middleware::on_outbound`]
+    /// Called with every serialized message sent to an actor on another system, which may be modified.
+    /// Messages sent to several actors at once (e.g. with [`Palantir::multicast`](crate::Palantir::multicast)) are only passed through once.
+    fn on_outbound(&self, _message_type: &str, _data: &mut Vec<u8>) {}
+}
+
+/// # [`Chain`]
+/// The middleware installed on an instance, in the order it was added, which is shared with the instance's senders.
+#[derive(Default)]
+pub(crate) struct Chain {
+    /// The installed middleware
+    middleware: RwLock<Vec<Arc<dyn Middleware>>>,
+}
+
+impl Chain {
+    /// # [`Chain::push`]
+    /// Adds middleware to the end of the chain.
+    pub fn push(&self, middleware: Arc<dyn Middleware>) {
+        self.middleware.write().unwrap_or_else(PoisonError::into_inner).push(middleware);
+    }
+
+    /// # [`Chain::clear`]
+    /// Removes all middleware.
+    pub fn clear(&self) {
+        self.middleware.write().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    /// # [`Chain::inbound`]
+    /// Passes an inbound request through the middleware, last added first, stopping at the first that rejects it.
+    pub fn inbound(&self, actor: &ActorID, message_type: &str, request: &mut Request) -> ControlFlow<ChannelError> {
+        for middleware in self.middleware.read().unwrap_or_else(PoisonError::into_inner).iter().rev() {
+            middleware.on_inbound(actor, message_type, request)?;
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// # [`Chain::outbound`]
+    /// Passes an outbound message through the middleware, first added first.
+    pub fn outbound(&self, message_type: &str, data: &mut Vec<u8>) {
+        for middleware in self.middleware.read().unwrap_or_else(PoisonError::into_inner).iter() {
+            middleware.on_outbound(message_type, data);
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use fluxion::{actor, message, ActorContext, Delegate, Handler, Identifier};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{testkit::two_systems, PalantirSendError};
+    use super::*;
+
+    #[actor]
+    struct Greeter;
+
+    #[message(String)]
+    #[derive(Serialize, Deserialize)]
+    struct Greet;
+
+    impl Handler<Greet> for Greeter {
+        async fn handle_message<D: Delegate>(&self, _message: Greet, _context: &ActorContext<D>) -> String {
+            "hello".to_string()
+        }
+    }
+
+    /// Appends its tag to outbound messages, and removes it from inbound ones, rejecting requests that don't end with it.
+    /// Logs every message it sees.
+    struct Tag {
+        tag: u8,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Tag {
+        fn on_inbound(&self, _actor: &ActorID, _message_type: &str, request: &mut Request) -> ControlFlow<ChannelError> {
+            self.log.lock().unwrap().push(format!("in {}", self.tag));
+
+            if request.data_mut().pop() == Some(self.tag) {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(ChannelError::Serialization("missing tag".to_string()))
+            }
+        }
+
+        fn on_outbound(&self, _message_type: &str, data: &mut Vec<u8>) {
+            self.log.lock().unwrap().push(format!("out {}", self.tag));
+            data.push(self.tag);
+        }
+    }
+
+    #[tokio::test]
+    async fn order() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Greeter).await.unwrap();
+        b.get_delegate().register::<Greeter, Greet, _>(b.get_local::<Greeter>(id).await.unwrap()).await;
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for tag in [1, 2] {
+            a.get_delegate().add_middleware(Tag { tag, log: log.clone() });
+            b.get_delegate().add_middleware(Tag { tag, log: log.clone() });
+        }
+
+        // Inbound requests unwrap the middleware in reverse, so the tags are removed in the order they were added
+        let sender = a.get::<Greeter, Greet>(Identifier::Foreign(id, "b")).await.unwrap();
+        assert_eq!(sender.send(Greet).await.unwrap(), "hello");
+        assert_eq!(*log.lock().unwrap(), ["out 1", "out 2", "in 2", "in 1"]);
+    }
+
+    #[tokio::test]
+    async fn rejected() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Greeter).await.unwrap();
+        b.get_delegate().register::<Greeter, Greet, _>(b.get_local::<Greeter>(id).await.unwrap()).await;
+
+        // Only b expects a tag, so it rejects the request before it reaches any later middleware or the actor
+        let log = Arc::new(Mutex::new(Vec::new()));
+        b.get_delegate().add_middleware(Tag { tag: 1, log: log.clone() });
+        b.get_delegate().add_middleware(Tag { tag: 2, log: log.clone() });
+
+        let sender = a.get::<Greeter, Greet>(Identifier::Foreign(id, "b")).await.unwrap();
+        let error = sender.send(Greet).await.unwrap_err();
+        assert!(matches!(PalantirSendError::of(&error), Some(PalantirSendError::Transport(ChannelError::Serialization(_)))));
+        assert_eq!(*log.lock().unwrap(), ["in 2"]);
+
+        // Without any middleware, the request is handled again
+        b.get_delegate().clear_middleware();
+        assert_eq!(sender.send(Greet).await.unwrap(), "hello");
+    }
+}
@@ -0,0 +1,201 @@
+//! # Outbox
+//! When enabled with [`Palantir::set_outbox`](crate::Palantir::set_outbox), messages sent to a system that can't currently be reached
+//! are held in an outbox instead of failing immediately, and are sent once the system is reachable again.
+//! This is useful for intermittently connected systems, such as edge nodes.
+
+use std::{collections::HashMap, sync::{Arc, Mutex, PoisonError}, time::Duration};
+
+
+
+/// # [`OutboxConfig`]
+/// Configures how palantir holds messages for unreachable systems.
+#[derive(Clone, Debug)]
+pub struct OutboxConfig {
+    /// How many messages may be held for a single system at once. Messages beyond this fail immediately.
+    pub capacity: usize,
+    /// How long a message is held before it fails.
+    pub ttl: Duration,
+    /// How often palantir tries to reach the system again while messages are held for it.
+    /// It also tries again whenever the backend connects to a peer, as long as the instance is serving.
+    pub retry_interval: Duration,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            ttl: Duration::from_secs(300),
+            retry_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// # [`Outbox`]
+/// Tracks how many messages are held for each system.
+#[derive(Default)]
+pub(crate) struct Outbox {
+    /// The outbox's configuration, or [`None`] if it is disabled
+    config: Mutex<Option<OutboxConfig>>,
+    /// How many messages are held for each system
+    held: Mutex<HashMap<String, usize>>,
+}
+
+impl Outbox {
+    /// # [`Outbox::set_config`]
+    /// Enables the outbox with the given configuration, or disables it if [`None`].
+    /// Messages that are already held keep using the configuration they were held with.
+    pub fn set_config(&self, config: Option<OutboxConfig>) {
+        *self.config.lock().unwrap_or_else(PoisonError::into_inner) = config;
+    }
+
+    /// # [`Outbox::config`]
+    /// Returns the outbox's configuration, or [`None`] if it is disabled.
+    pub fn config(&self) -> Option<OutboxConfig> {
+        self.config.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// # [`Outbox::is_enabled`]
+    /// Returns whether messages are currently held for unreachable systems.
+    pub fn is_enabled(&self) -> bool {
+        self.config.lock().unwrap_or_else(PoisonError::into_inner).is_some()
+    }
+
+    /// # [`Outbox::hold`]
+    /// Reserves a place in the outbox for a message to the given system, returning it alongside the outbox's configuration.
+    /// Returns [`None`] if the outbox is disabled or the system's messages are at capacity.
+    pub fn hold(self: &Arc<Self>, system: &str) -> Option<(Held, OutboxConfig)> {
+        let config = self.config.lock().unwrap_or_else(PoisonError::into_inner).clone()?;
+
+        let mut held = self.held.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = held.entry(system.to_string()).or_default();
+
+        if *count >= config.capacity {
+            return None;
+        }
+        *count += 1;
+
+        Some((Held {
+            outbox: self.clone(),
+            system: system.to_string(),
+        }, config))
+    }
+}
+
+/// # [`Held`]
+/// A message's place in the [`Outbox`], which is released when dropped.
+pub(crate) struct Held {
+    /// The outbox the message is held in
+    outbox: Arc<Outbox>,
+    /// The system the message is for
+    system: String,
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        let mut held = self.outbox.held.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(count) = held.get_mut(&self.system) {
+            *count -= 1;
+
+            if *count == 0 {
+                held.remove(&self.system);
+            }
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use fluxion::{actor, message, ActorContext, Delegate, Fluxion, Handler, Identifier};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{backend::memory::{MemoryBackend, MemoryNetwork}, Palantir};
+    use super::*;
+
+    /// How many messages the counters received, across every system
+    static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+    #[actor]
+    struct Counter;
+
+    #[message]
+    #[derive(Serialize, Deserialize)]
+    struct Count;
+
+    impl Handler<Count> for Counter {
+        async fn handle_message<D: Delegate>(&self, _message: Count, _context: &ActorContext<D>) {
+            RECEIVED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Starts a system on the network serving a counter, returning it alongside the counter's id and its serving task.
+    async fn counter(network: &MemoryNetwork) -> (Fluxion<Palantir<MemoryBackend>>, u64, tokio::task::JoinHandle<()>) {
+        let system = Fluxion::new("b", Palantir::new("b".to_string(), network.backend("b")).unwrap());
+
+        // Register before serving, so that nothing reaches the system before the counter can handle it
+        let id = system.add(Counter).await.unwrap();
+        system.get_delegate().register::<Counter, Count, _>(system.get_local::<Counter>(id).await.unwrap()).await;
+
+        let serving = system.clone();
+        let serving = tokio::spawn(async move { serving.get_delegate().serve().await });
+
+        (system, id, serving)
+    }
+
+    #[test]
+    fn capacity() {
+        let outbox = Arc::new(Outbox::default());
+        assert!(outbox.hold("b").is_none());
+
+        outbox.set_config(Some(OutboxConfig { capacity: 1, ..OutboxConfig::default() }));
+        let held = outbox.hold("b").unwrap();
+        assert!(outbox.hold("b").is_none());
+        assert!(outbox.hold("c").is_some());
+
+        // Releasing a message makes room for another
+        drop(held);
+        assert!(outbox.hold("b").is_some());
+    }
+
+    #[tokio::test]
+    async fn held_until_reconnected() {
+        let network = MemoryNetwork::new();
+        let a = Fluxion::new("a", Palantir::new("a".to_string(), network.backend("a")).unwrap());
+        let serving = a.clone();
+        let _serving = tokio::spawn(async move { serving.get_delegate().serve().await });
+
+        // Only a peer connecting can release the message in time
+        a.get_delegate().set_outbox(Some(OutboxConfig {
+            retry_interval: Duration::from_secs(60),
+            ..OutboxConfig::default()
+        }));
+
+        let (b, id, serving) = counter(&network).await;
+        let sender = a.get::<Counter, Count>(Identifier::Foreign(id, "b")).await.unwrap();
+        sender.send(Count).await.unwrap();
+        assert_eq!(RECEIVED.load(Ordering::Relaxed), 1);
+
+        // Take the system off the network
+        serving.abort();
+        let _ = serving.await;
+        assert!(b.get_delegate().unregister::<Counter, Count>(id).await);
+        b.shutdown().await;
+        drop(b);
+        assert_eq!(network.systems(), ["a"]);
+
+        let send = tokio::spawn(async move { sender.send(Count).await.is_ok() });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!send.is_finished());
+
+        let (_b, reconnected, _serving) = counter(&network).await;
+        assert_eq!(reconnected, id);
+
+        assert!(tokio::time::timeout(Duration::from_secs(5), send).await.unwrap().unwrap());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(RECEIVED.load(Ordering::Relaxed), 2);
+    }
+}
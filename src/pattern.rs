@@ -0,0 +1,86 @@
+//! # Pattern
+//! Actors can be registered to handle requests for every named actor matching a pattern, such as `worker-*`,
+//! instead of just for themselves. This lets a single dispatcher actor serve a dynamic family of logical actors.
+//! These registrations receive their messages wrapped in [`Routed`], which carries the actor that was actually requested.
+
+use fluxion::Message;
+
+use crate::ActorID;
+
+
+
+/// # [`Routed`]
+/// A message received by a pattern registration, alongside the actor it was sent to.
+pub struct Routed<M> {
+    /// The actor that the message was sent to
+    pub actor: ActorID,
+    /// The message itself
+    pub message: M,
+}
+
+impl<M: Message> Message for Routed<M> {
+    type Result = M::Result;
+}
+
+/// # [`matches`]
+/// Checks whether the given actor name matches the given pattern, in which `*` matches any (possibly empty) run of characters.
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+
+    let mut parts = pattern.split('*');
+
+    // The first part has to be at the start of the name
+    let Some(rest) = name.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+
+    // If the pattern had no wildcards, the whole name has to match
+    let Some(last) = parts.next_back() else {
+        return rest.is_empty();
+    };
+
+    // The middle parts have to appear in order, as early as possible so the rest can still match
+    let mut rest = rest;
+    for part in parts {
+        let Some(index) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[index + part.len()..];
+    }
+
+    // And the last part has to be at the end
+    rest.ends_with(last)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn literal() {
+        assert!(matches("worker", "worker"));
+        assert!(!matches("worker", "worker-1"));
+        assert!(!matches("worker", "work"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("worker-*", "worker-1"));
+        assert!(matches("worker-*", "worker-"));
+        assert!(!matches("worker-*", "worker"));
+        assert!(matches("*-eu", "worker-eu"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "a-b-c"));
+        assert!(matches("a*b*c", "abc"));
+        assert!(!matches("a*b*c", "a-c-b"));
+    }
+
+    #[test]
+    fn parts_do_not_overlap() {
+        assert!(!matches("ab*ba", "aba"));
+        assert!(matches("ab*ba", "abba"));
+        assert!(!matches("*a*a", "a"));
+        assert!(matches("a*bc*c", "abcc"));
+    }
+}
@@ -0,0 +1,122 @@
+//! # Probe
+//! Every palantir instance echoes back requests of a reserved probe message type, without any registration.
+//! [`Palantir::probe`](crate::Palantir::probe) uses this to verify connectivity to a system and measure the link's
+//! latency and throughput.
+
+use std::time::Duration;
+
+use fluxion::{Message, MessageID};
+
+
+
+/// # [`PROBE_ACTOR`]
+/// The actor probes are addressed to.
+pub(crate) const PROBE_ACTOR: &str = "palantir::probe";
+
+/// # [`Probe`]
+/// The message type of probes, whose payload is echoed back as is.
+pub(crate) struct Probe;
+
+impl Message for Probe {
+    /// The echoed payload
+    type Result = Vec<u8>;
+}
+
+impl MessageID for Probe {
+    const ID: &'static str = "palantir::probe::Probe";
+}
+
+/// # [`ProbeStats`]
+/// The results of probing a system.
+#[derive(Clone, Debug, Default)]
+pub struct ProbeStats {
+    /// How many probes were sent
+    pub sent: usize,
+    /// How many probes were echoed back intact
+    pub received: usize,
+    /// The shortest round trip time of a probe that was echoed back
+    pub min_latency: Duration,
+    /// The mean round trip time of the probes that were echoed back
+    pub mean_latency: Duration,
+    /// The longest round trip time of a probe that was echoed back
+    pub max_latency: Duration,
+    /// The payload bytes sent and echoed back per second, over the probes that were echoed back
+    pub throughput: f64,
+}
+
+impl ProbeStats {
+    /// # [`ProbeStats::from_latencies`]
+    /// Computes the statistics of the given number of probes with the given payload size,
+    /// of which the given round trip times were echoed back.
+    pub(crate) fn from_latencies(sent: usize, payload_size: usize, latencies: &[Duration]) -> Self {
+        let total = latencies.iter().sum::<Duration>();
+
+        #[allow(clippy::cast_precision_loss)]
+        let throughput = if total.is_zero() {
+            0.0
+        } else {
+            (2 * payload_size * latencies.len()) as f64 / total.as_secs_f64()
+        };
+
+        Self {
+            sent,
+            received: latencies.len(),
+            min_latency: latencies.iter().min().copied().unwrap_or_default(),
+            mean_latency: u32::try_from(latencies.len()).ok()
+                .and_then(|count| total.checked_div(count))
+                .unwrap_or_default(),
+            max_latency: latencies.iter().max().copied().unwrap_or_default(),
+            throughput,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::OpenChannelError, testkit::two_systems};
+    use super::*;
+
+    #[test]
+    fn statistics() {
+        let latencies = [Duration::from_millis(10), Duration::from_millis(30), Duration::from_millis(20)];
+        let stats = ProbeStats::from_latencies(4, 100, &latencies);
+
+        assert_eq!((stats.sent, stats.received), (4, 3));
+        assert_eq!(stats.min_latency, Duration::from_millis(10));
+        assert_eq!(stats.mean_latency, Duration::from_millis(20));
+        assert_eq!(stats.max_latency, Duration::from_millis(30));
+        // 600 bytes there and back over 60ms
+        assert!((stats.throughput - 10_000.0).abs() < 1e-6);
+
+        // Nothing echoed back
+        let stats = ProbeStats::from_latencies(4, 100, &[]);
+        assert_eq!(stats.received, 0);
+        assert_eq!(stats.mean_latency, Duration::ZERO);
+        assert!(stats.throughput.abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn probe() {
+        let (a, _b, _guard) = two_systems("a", "b");
+
+        // No registration is needed on the probed system
+        let stats = a.get_delegate().probe("b", 256, 5).await.unwrap();
+        assert_eq!((stats.sent, stats.received), (5, 5));
+        assert!(stats.min_latency <= stats.mean_latency && stats.mean_latency <= stats.max_latency);
+        assert!(stats.throughput > 0.0);
+    }
+
+    #[tokio::test]
+    async fn probe_rejected() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        // Probes over the probed system's payload limit aren't echoed
+        b.get_delegate().set_max_payload_size(Some(64));
+        let stats = a.get_delegate().probe("b", 256, 3).await.unwrap();
+        assert_eq!((stats.sent, stats.received), (3, 0));
+
+        assert!(matches!(a.get_delegate().probe("c", 256, 3).await, Err(OpenChannelError::SystemUnreachable(_))));
+    }
+}
@@ -0,0 +1,78 @@
+//! # Query
+//! [`Palantir::query_actor`](crate::Palantir::query_actor) asks a foreign system whether an actor exists and handles
+//! a message type, before any messages are sent to it. The query is addressed to the actor itself, and is answered by
+//! the receiving palantir instance from its registrations without involving the actor.
+
+use fluxion::{Message, MessageID};
+use serde::{Deserialize, Serialize};
+
+
+
+/// # [`Query`]
+/// Asks whether the actor the request is addressed to handles the given message type.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Query {
+    /// The message type the actor should handle
+    pub message_type: String,
+}
+
+impl Message for Query {
+    /// Whether the actor handles the message type, still serialized
+    type Result = Vec<u8>;
+}
+
+impl MessageID for Query {
+    const ID: &'static str = "palantir::query::Query";
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, message, ActorContext, Delegate, Handler};
+
+    use crate::{pattern::Routed, testkit::two_systems, ActorID};
+    use super::*;
+
+    #[actor]
+    struct Store;
+
+    #[message(bool)]
+    #[derive(Serialize, Deserialize)]
+    struct Put;
+
+    impl Handler<Put> for Store {
+        async fn handle_message<D: Delegate>(&self, _message: Put, _context: &ActorContext<D>) -> bool {
+            true
+        }
+    }
+
+    impl Handler<Routed<Put>> for Store {
+        async fn handle_message<D: Delegate>(&self, _message: Routed<Put>, _context: &ActorContext<D>) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn query() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Store).await.unwrap();
+        b.get_delegate().register::<Store, Put, _>(b.get_local::<Store>(id).await.unwrap()).await;
+        b.get_delegate().register_pattern::<Store, Put, _>("store-*".to_string(), b.get_local::<Store>(id).await.unwrap()).await;
+
+        let a = a.get_delegate();
+        assert!(a.query_actor("b", ActorID::Numeric(id), Put::ID).await.unwrap());
+        assert!(a.query_actor("b", ActorID::Named("store-1".to_string()), Put::ID).await.unwrap());
+
+        // Other actors and message types aren't handled
+        assert!(!a.query_actor("b", ActorID::Numeric(id), "other").await.unwrap());
+        assert!(!a.query_actor("b", ActorID::Numeric(id + 1), Put::ID).await.unwrap());
+        assert!(!a.query_actor("b", ActorID::Named("cache-1".to_string()), Put::ID).await.unwrap());
+
+        // Neither are ones that aren't registered anymore
+        b.get_delegate().unregister::<Store, Put>(id).await;
+        assert!(!a.query_actor("b", ActorID::Numeric(id), Put::ID).await.unwrap());
+
+        assert!(a.query_actor("c", ActorID::Numeric(id), Put::ID).await.is_err());
+    }
+}
@@ -0,0 +1,307 @@
+//! # Registration
+//! Every registration queues its inbound requests, and handles each in its own task. [`RegistrationConfig`] bounds both,
+//! so that an actor under heavy load can't exhaust memory. It is passed to [`Palantir::register_with_config`](crate::Palantir::register_with_config)
+//! or [`Palantir::register_pattern_with_config`](crate::Palantir::register_pattern_with_config).
+//!
+//! An actor can also be registered for several message types at once with a [`RegistrationBuilder`], in which case
+//! all of them share a single queue and relay task, and so a single [`RegistrationConfig`].
+
+use std::{any::Any, sync::Arc};
+
+use fluxion::{Actor, Delegate, Handler, IndeterminateMessage, LocalRef, MessageSender};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{info, serializer::Serializer, Event, Palantir, Queued, Registration};
+
+
+
+/// # [`OverflowPolicy`]
+/// What happens to an inbound request when its registration's queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// # [`OverflowPolicy::Block`]
+    /// Wait for space in the queue, which holds up every other request palantir receives in the meantime.
+    #[default]
+    Block,
+    /// # [`OverflowPolicy::Reject`]
+    /// Respond to the request with [`ChannelError::Overloaded`](crate::backend::ChannelError::Overloaded).
+    Reject,
+    /// # [`OverflowPolicy::Drop`]
+    /// Drop the request without responding to it.
+    Drop,
+}
+
+/// # [`RegistrationConfig`]
+/// Configures how a registration queues and handles inbound requests.
+#[derive(Clone, Debug)]
+pub struct RegistrationConfig {
+    /// How many requests may be queued before the overflow policy applies. This is at least one.
+    pub queue_depth: usize,
+    /// How many requests may be handled at once, or [`None`] if there is no limit.
+    /// Requests beyond this stay queued until one finishes.
+    pub max_concurrent: Option<usize>,
+    /// What happens to requests once the queue is full.
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for RegistrationConfig {
+    fn default() -> Self {
+        Self {
+            queue_depth: 256,
+            max_concurrent: None,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+/// # [`Typed`]
+/// Creates one message type's [`Registration`] once the relay task it shares is spawned,
+/// returning it alongside the message type and the local sender for it.
+type Typed<'a, B, S> = Box<dyn FnOnce(&Palantir<B, S>, mpsc::Sender<Queued>, OverflowPolicy) -> (&'static str, Registration, Box<dyn Any + Send + Sync>) + Send + 'a>;
+
+/// # [`RegistrationBuilder`]
+/// Registers an actor for several message types at once, all of which share a single queue and relay task.
+/// Created with [`Palantir::registration`](crate::Palantir::registration), and registered once
+/// [`RegistrationBuilder::register`] is awaited.
+#[must_use = "nothing is registered until `RegistrationBuilder::register` is awaited"]
+pub struct RegistrationBuilder<'a, A: Actor, D: Delegate, B, S> {
+    /// The instance the actor is registered with
+    palantir: &'a Palantir<B, S>,
+    /// The actor being registered
+    actor: LocalRef<A, D>,
+    /// How the shared relay task queues and handles requests
+    config: RegistrationConfig,
+    /// The message types the actor is registered for, in order
+    types: Vec<Typed<'a, B, S>>,
+}
+
+impl<'a, A: Actor, D: Delegate + AsRef<Palantir<B, S>>, B, S: Serializer> RegistrationBuilder<'a, A, D, B, S> {
+    /// # [`RegistrationBuilder::new`]
+    /// Creates a builder for the given actor, with the default [`RegistrationConfig`] and no message types.
+    pub(crate) fn new(palantir: &'a Palantir<B, S>, actor: LocalRef<A, D>) -> Self {
+        Self {
+            palantir,
+            actor,
+            config: RegistrationConfig::default(),
+            types: Vec::new(),
+        }
+    }
+
+    /// # [`RegistrationBuilder::with_config`]
+    /// Queues and handles the requests for every message type as configured. The limits apply to all of them together.
+    pub fn with_config(mut self, config: RegistrationConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// # [`RegistrationBuilder::handles`]
+    /// Registers the actor for the message type `M`, like [`Palantir::register`](crate::Palantir::register).
+    pub fn handles<M: IndeterminateMessage>(mut self) -> Self
+        where A: Handler<M>, M::Result: Serialize + for<'de> Deserialize<'de> {
+
+        let actor = self.actor.clone();
+        self.types.push(Box::new(move |palantir, sender, overflow| {
+            let local: Arc<dyn MessageSender<M>> = Arc::new(actor.clone());
+            let registration = palantir.new_registration::<A, M, M, D>(actor, sender, overflow, |_, message| message);
+            (M::ID, registration, Box::new(local))
+        }));
+
+        self
+    }
+
+    /// # [`RegistrationBuilder::register`]
+    /// Registers the actor for every message type, replacing any existing registrations of the actor for them.
+    /// 
+    /// # Panics
+    /// Panics if the join set mutex or local senders lock is poisoned, which should never happen.
+    pub async fn register(self) {
+        let id = self.actor.get_id();
+        let sender = self.palantir.spawn_relay(id, &self.config);
+
+        for typed in self.types {
+            let (message_type, registration, local) = typed(self.palantir, sender.clone(), self.config.overflow);
+
+            info!(system = %self.palantir.system_id, actor = id, message_type, "registering actor");
+
+            self.palantir.actor_handlers.write().await
+                .insert((id, message_type.to_string()), registration);
+            self.palantir.local_senders.write().expect("local senders lock should never be poisoned")
+                .insert((id, message_type.to_string()), local);
+
+            // Publish the registration. Nobody listening is not an error.
+            let _ = self.palantir.events.send(Event::RegistrationAdded { actor: id, message_type });
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use fluxion::{actor, message, ActorContext, Fluxion, Identifier, MessageID, MessageSendError};
+
+    use crate::{backend::{memory::MemoryBackend, ChannelError}, testkit::two_systems, PalantirSendError};
+    use super::*;
+
+    #[actor]
+    struct Sleeper;
+
+    /// Sleeps for the given number of milliseconds, and returns them
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Sleep(u64);
+
+    /// Returns the given value without sleeping
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Echo(u64);
+
+    impl Handler<Sleep> for Sleeper {
+        async fn handle_message<D: Delegate>(&self, message: Sleep, _context: &ActorContext<D>) -> u64 {
+            tokio::time::sleep(Duration::from_millis(message.0)).await;
+            message.0
+        }
+    }
+
+    impl Handler<Echo> for Sleeper {
+        async fn handle_message<D: Delegate>(&self, message: Echo, _context: &ActorContext<D>) -> u64 {
+            message.0
+        }
+    }
+
+    type System = Fluxion<Palantir<MemoryBackend>>;
+
+    /// Sends the message from `a` to the given actor on `b` in its own task
+    fn send<M: IndeterminateMessage>(a: &System, actor: u64, message: M) -> tokio::task::JoinHandle<Result<M::Result, Option<ChannelError>>>
+        where Sleeper: Handler<M>, M::Result: Serialize + for<'de> Deserialize<'de> {
+
+        let a = a.clone();
+        tokio::spawn(async move {
+            let sender = a.get::<Sleeper, M>(Identifier::Foreign(actor, "b")).await.unwrap();
+            sender.send(message).await.map_err(|e| transport_error(&e))
+        })
+    }
+
+    /// Returns the transport error the request failed with, if it failed with one
+    fn transport_error(error: &MessageSendError) -> Option<ChannelError> {
+        match PalantirSendError::of(error) {
+            Some(PalantirSendError::Transport(error)) => Some(error.clone()),
+            _ => None,
+        }
+    }
+
+    /// Waits until the given registration's requests are queued and being handled as expected
+    async fn wait_stats(b: &System, actor: u64, message_type: &str, handling: usize, queue_depth: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let stats = b.get_delegate().stats()[&(actor, message_type.to_string())].clone();
+                if stats.handling == handling && stats.queue_depth == queue_depth {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).await.expect("requests should be queued and handled");
+    }
+
+    /// Registers a sleeper on `b` that handles one request at a time and queues one more, overflowing as given.
+    /// Returns its id once a slow request is being handled and another is queued behind it, alongside both of them.
+    async fn full_queue(a: &System, b: &System, overflow: OverflowPolicy) -> (u64, [tokio::task::JoinHandle<Result<u64, Option<ChannelError>>>; 2]) {
+        let id = b.add(Sleeper).await.unwrap();
+        let config = RegistrationConfig { queue_depth: 1, max_concurrent: Some(1), overflow };
+        b.get_delegate().register_with_config::<Sleeper, Sleep, _>(b.get_local::<Sleeper>(id).await.unwrap(), config).await;
+
+        let handling = send(a, id, Sleep(100));
+        wait_stats(b, id, Sleep::ID, 1, 0).await;
+        let queued = send(a, id, Sleep(1));
+        wait_stats(b, id, Sleep::ID, 1, 1).await;
+
+        (id, [handling, queued])
+    }
+
+    #[tokio::test]
+    async fn overflow_reject() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let (id, [handling, queued]) = full_queue(&a, &b, OverflowPolicy::Reject).await;
+
+        assert!(matches!(send(&a, id, Sleep(1)).await.unwrap(), Err(Some(ChannelError::Overloaded))));
+        assert_eq!(handling.await.unwrap().unwrap(), 100);
+        assert_eq!(queued.await.unwrap().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn overflow_drop() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let (id, [handling, queued]) = full_queue(&a, &b, OverflowPolicy::Drop).await;
+
+        // The request is dropped without a response, rather than being rejected
+        let dropped = tokio::time::timeout(Duration::from_secs(5), send(&a, id, Sleep(1))).await.unwrap().unwrap();
+        assert!(!matches!(dropped, Ok(_) | Err(Some(ChannelError::Overloaded))));
+        assert_eq!(handling.await.unwrap().unwrap(), 100);
+        assert_eq!(queued.await.unwrap().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn overflow_block() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let (id, [handling, queued]) = full_queue(&a, &b, OverflowPolicy::Block).await;
+
+        // The request waits for space in the queue, and is handled once there is some
+        let blocked = send(&a, id, Sleep(1));
+        wait_stats(&b, id, Sleep::ID, 1, 2).await;
+        assert_eq!(handling.await.unwrap().unwrap(), 100);
+        assert_eq!(queued.await.unwrap().unwrap(), 1);
+        assert_eq!(blocked.await.unwrap().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn max_concurrent() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Sleeper).await.unwrap();
+        let config = RegistrationConfig { max_concurrent: Some(2), ..Default::default() };
+        b.get_delegate().register_with_config::<Sleeper, Sleep, _>(b.get_local::<Sleeper>(id).await.unwrap(), config).await;
+
+        // Requests beyond the cap stay queued
+        let requests = (0..4).map(|_| send(&a, id, Sleep(100))).collect::<Vec<_>>();
+        wait_stats(&b, id, Sleep::ID, 2, 2).await;
+
+        for request in requests {
+            assert_eq!(request.await.unwrap().unwrap(), 100);
+        }
+        wait_stats(&b, id, Sleep::ID, 0, 0).await;
+    }
+
+    #[tokio::test]
+    async fn builder() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let mut events = b.get_delegate().events();
+
+        let id = b.add(Sleeper).await.unwrap();
+        b.get_delegate().registration(b.get_local::<Sleeper>(id).await.unwrap())
+            .with_config(RegistrationConfig { max_concurrent: Some(1), ..Default::default() })
+            .handles::<Sleep>()
+            .handles::<Echo>()
+            .register().await;
+
+        // Both message types are registered, in order
+        let mut added = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::RegistrationAdded { actor, message_type } = event {
+                assert_eq!(actor, id);
+                added.push(message_type);
+            }
+        }
+        assert_eq!(added, [Sleep::ID, Echo::ID]);
+
+        // Both are handled, and share the concurrency limit, so an echo waits for a slow request of the other type
+        let slow = send(&a, id, Sleep(100));
+        wait_stats(&b, id, Sleep::ID, 1, 0).await;
+        let echo = send(&a, id, Echo(7));
+        wait_stats(&b, id, Echo::ID, 0, 1).await;
+
+        assert_eq!(slow.await.unwrap().unwrap(), 100);
+        assert_eq!(echo.await.unwrap().unwrap(), 7);
+    }
+}
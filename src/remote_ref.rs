@@ -0,0 +1,159 @@
+//! # Remote references
+//! A [`RemoteRef`] names an actor on some system that handles a specific message type. It can be embedded in messages,
+//! and resolved into a [`MessageSender`](fluxion::MessageSender) by the receiving system with
+//! [`Palantir::resolve`](crate::Palantir::resolve), which lets actors pass "reply to this actor" handles across the network.
+
+use std::marker::PhantomData;
+
+use fluxion::MessageID;
+use serde::{Deserialize, Serialize};
+
+use crate::ActorID;
+
+
+
+/// # [`RemoteRef`]
+/// A serializable reference to an actor on a specific system that handles messages of type `M`.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct RemoteRef<M> {
+    /// The system the actor is on
+    system: String,
+    /// The actor itself
+    actor: ActorID,
+    /// The message type the reference was created for, which is checked when resolving it
+    message_type: String,
+    /// The message type, which the reference is typed over without containing any
+    #[serde(skip)]
+    _phantom: PhantomData<fn() -> M>,
+}
+
+impl<M: MessageID> RemoteRef<M> {
+    /// # [`RemoteRef::new`]
+    /// Creates a reference to the given actor on the given system.
+    #[must_use]
+    pub fn new(system: String, actor: ActorID) -> Self {
+        Self {
+            system,
+            actor,
+            message_type: M::ID.to_string(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// # [`RemoteRef::is_valid`]
+    /// Checks whether the reference was created for the message type `M`. A reference can be deserialized as the wrong
+    /// message type, as the message type isn't known until it is resolved.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.message_type == M::ID
+    }
+}
+
+impl<M> RemoteRef<M> {
+    /// # [`RemoteRef::system`]
+    /// Returns the system the actor is on.
+    #[must_use]
+    pub fn system(&self) -> &str {
+        &self.system
+    }
+
+    /// # [`RemoteRef::actor`]
+    /// Returns the actor being referenced.
+    #[must_use]
+    pub fn actor(&self) -> &ActorID {
+        &self.actor
+    }
+
+    /// # [`RemoteRef::message_type`]
+    /// Returns the message type the reference was created for.
+    #[must_use]
+    pub fn message_type(&self) -> &str {
+        &self.message_type
+    }
+}
+
+impl<M> Clone for RemoteRef<M> {
+    fn clone(&self) -> Self {
+        Self {
+            system: self.system.clone(),
+            actor: self.actor.clone(),
+            message_type: self.message_type.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<M> std::fmt::Debug for RemoteRef<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteRef")
+            .field("system", &self.system)
+            .field("actor", &self.actor)
+            .field("message_type", &self.message_type)
+            .finish()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, message, ActorContext, Delegate, Handler};
+
+    use crate::{backend::OpenChannelError, serializer::Pot, testkit::{decode, encode, two_systems}};
+    use super::*;
+
+    #[actor]
+    struct Counter;
+
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Double(u64);
+
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Halve(u64);
+
+    impl Handler<Double> for Counter {
+        async fn handle_message<D: Delegate>(&self, message: Double, _context: &ActorContext<D>) -> u64 {
+            message.0 * 2
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        let id = b.add(Counter).await.unwrap();
+        b.get_delegate().register::<Counter, Double, _>(b.get_local::<Counter>(id).await.unwrap()).await;
+
+        // The reference is created on b, sent to a, and resolved there
+        let data = encode::<Pot>(&b.get_delegate().remote_ref::<Double>(id));
+        let reference: RemoteRef<Double> = decode::<Pot, _>(&data);
+        assert!(reference.is_valid());
+        assert_eq!(reference.system(), "b");
+        assert_eq!(reference.actor(), &ActorID::Numeric(id));
+
+        let sender = a.get_delegate().resolve(&reference).await.unwrap();
+        assert_eq!(sender.send(Double(21)).await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn wrong_message_type() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        let id = b.add(Counter).await.unwrap();
+        b.get_delegate().register::<Counter, Double, _>(b.get_local::<Counter>(id).await.unwrap()).await;
+
+        // Deserializing the reference as another message type succeeds, but resolving it doesn't
+        let data = encode::<Pot>(&b.get_delegate().remote_ref::<Double>(id));
+        let reference: RemoteRef<Halve> = decode::<Pot, _>(&data);
+        assert!(!reference.is_valid());
+
+        match a.get_delegate().resolve(&reference).await {
+            Err(OpenChannelError::MessageNotHandled(message_type)) => assert_eq!(message_type, Double::ID),
+            Err(other) => panic!("expected MessageNotHandled, got {other:?}"),
+            Ok(_) => panic!("expected MessageNotHandled, got a sender"),
+        }
+    }
+}
@@ -5,41 +5,67 @@
 
 use tokio::sync::oneshot;
 
+use crate::backend::ChannelError;
+
 /// # [`Request`]
-/// Basic struct that provides request/response semantics over mpsc channels
+/// Basic struct that provides request/response semantics over mpsc channels.
+/// Backends construct these for inbound requests, and transmit whatever the request is responded with back to the sender.
 pub struct Request {
     /// The request's data
     pub(crate) data: Vec<u8>,
+    /// The system the request came from, and an id unique among that system's requests, if the backend provides them.
+    pub(crate) id: Option<(String, u64)>,
     /// The request's responder
-    pub(crate) responder: oneshot::Sender<Vec<u8>>
+    pub(crate) responder: oneshot::Sender<Result<Vec<u8>, ChannelError>>
 }
 
 impl Request {
     /// # [`Request::new`]
     /// Creates a new [`Request`] instance with the given data,
     /// returning the [`Request`] and the response [`oneshot`]
-    pub fn new(data: Vec<u8>) -> (Self, oneshot::Receiver<Vec<u8>>) {
+    #[must_use]
+    pub fn new(data: Vec<u8>) -> (Self, oneshot::Receiver<Result<Vec<u8>, ChannelError>>) {
 
         let (responder, response) = oneshot::channel();
 
         (Self {
             data,
+            id: None,
             responder,
         }, response)
     }
 
+    /// # [`Request::with_id`]
+    /// Creates a new [`Request`] like [`Request::new`], identified by the system it came from and an id
+    /// that is unique among that system's requests. When the same request is delivered more than once (e.g. because it was retried),
+    /// backends should deliver it with the same id, so that palantir is able to deduplicate it.
+    #[must_use]
+    pub fn with_id(data: Vec<u8>, source: String, id: u64) -> (Self, oneshot::Receiver<Result<Vec<u8>, ChannelError>>) {
+        let (mut request, response) = Self::new(data);
+        request.id = Some((source, id));
+        (request, response)
+    }
+
     /// # [`Request::data`]
     /// Returns the request's data.
+    #[must_use]
     pub fn data(&self) -> &[u8] {
         &self.data
     }
 
+    /// # [`Request::data_mut`]
+    /// Returns the request's data mutably, e.g. so that [`Middleware`](crate::middleware::Middleware) can transform it before it is handled.
+    pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+
     /// # [`Request::respond`]
     /// Responds to the request, consuming this request object.
+    /// The response is either the serialized result, or a [`ChannelError`] describing why the request could not be handled.
     /// 
     /// # Errors
-    /// If the response fails, this returns the response data as an error.
-    pub fn respond(self, response: Vec<u8>) -> Result<(), Vec<u8>> {
+    /// If the response fails, this returns the response as an error.
+    pub fn respond(self, response: Result<Vec<u8>, ChannelError>) -> Result<(), Result<Vec<u8>, ChannelError>> {
         self.responder.send(response)
     }
 }
\ No newline at end of file
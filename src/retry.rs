@@ -0,0 +1,231 @@
+//! # Retry
+//! Senders can retry requests that fail transiently, as configured by the [`RetryPolicy`] set with
+//! [`Palantir::set_retry_policy`](crate::Palantir::set_retry_policy). Whether a failure is safe to retry is reported by the backend's
+//! channel (see [`Channel::is_retryable`](crate::backend::Channel::is_retryable)), and failures after which the actor may
+//! have handled the message are only retried if the policy allows it, as the actor may then handle the message more than once.
+
+use std::{collections::hash_map::RandomState, hash::{BuildHasher, Hasher}, time::Duration};
+
+
+
+/// # [`RetryOn`]
+/// Which failures a [`RetryPolicy`] retries. Each variant retries everything the ones before it do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RetryOn {
+    /// # [`RetryOn::Safe`]
+    /// Only retry failures that are known to have happened before the actor received the message,
+    /// such as the system being unreachable, or the channel reporting the failure as retryable.
+    #[default]
+    Safe,
+    /// # [`RetryOn::Transient`]
+    /// Also retry failures after which the actor may have handled the message, such as the peer disconnecting or the request timing out.
+    /// This should only be used for messages that are safe to handle more than once.
+    Transient,
+}
+
+/// # [`RetryPolicy`]
+/// Configures how senders retry failed requests. The delay before each retry grows exponentially from
+/// `initial_backoff` by `multiplier`, up to `max_backoff`, and a random portion of up to `jitter` of it is skipped,
+/// so that many senders failing at once don't all retry at once.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// How many times a request is attempted in total, including the first attempt. A request is always attempted at least once.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// The longest delay before any retry.
+    pub max_backoff: Duration,
+    /// How much the delay grows after each retry. Values below one are treated as one.
+    pub multiplier: f64,
+    /// The largest fraction of each delay that is randomly skipped, between zero and one.
+    pub jitter: f64,
+    /// Which failures are retried.
+    pub retry_on: RetryOn,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.5,
+            retry_on: RetryOn::Safe,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// # [`RetryPolicy::retries`]
+    /// Returns whether a request that failed on the given attempt, counting from one, should be retried.
+    /// The failure is classified by the kind of failures it is retried on, or [`None`] if it is never retried.
+    pub(crate) fn retries(&self, attempt: u32, failure: Option<RetryOn>) -> bool {
+        attempt < self.max_attempts && failure.is_some_and(|failure| failure <= self.retry_on)
+    }
+
+    /// # [`RetryPolicy::backoff`]
+    /// Returns how long to wait before retrying a request that failed on the given attempt, counting from one.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let mut delay = self.initial_backoff.min(self.max_backoff);
+        for _ in 1..attempt {
+            delay = Duration::try_from_secs_f64(delay.as_secs_f64() * self.multiplier.max(1.0))
+                .unwrap_or(self.max_backoff)
+                .min(self.max_backoff);
+        }
+
+        // A new random state is randomly seeded, which is plenty for spreading out retries
+        let random = u32::try_from(RandomState::new().build_hasher().finish() >> 32).unwrap_or(u32::MAX);
+        let skipped = self.jitter.clamp(0.0, 1.0) * f64::from(random) / f64::from(u32::MAX);
+
+        delay.mul_f64(1.0 - skipped)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+    use fluxion::{actor, message, ActorContext, Delegate, Fluxion, Handler, Identifier};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{backend::ChannelError, serializer::Pot, testkit::{encode, MockBackend}, Palantir, PalantirSendError};
+    use super::*;
+
+    #[test]
+    fn backoff_grows_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(64), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn backoff_is_jittered() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+            jitter: 0.5,
+            ..RetryPolicy::default()
+        };
+
+        for attempt in 1..=4 {
+            let delay = Duration::from_millis(100 * 2u64.pow(attempt - 1)).min(policy.max_backoff);
+
+            for _ in 0..32 {
+                let backoff = policy.backoff(attempt);
+                assert!(backoff <= delay && backoff >= delay / 2, "{backoff:?} out of bounds for {delay:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_parameters() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(1),
+            multiplier: 0.5,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+
+        // The initial backoff is capped, and the delay never shrinks
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(1));
+
+        let policy = RetryPolicy { jitter: 2.0, ..policy };
+        assert!(policy.backoff(1) <= Duration::from_secs(1));
+    }
+
+    /// Returns a system whose requests to other systems fail with the given errors in turn, and then succeed,
+    /// alongside how many requests were sent.
+    fn failing(errors: Vec<ChannelError>) -> (Fluxion<Palantir<MockBackend>>, Arc<AtomicUsize>) {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sent_clone = sent.clone();
+
+        let backend = MockBackend::new(move |_, _, _, _| {
+            let attempt = sent_clone.fetch_add(1, Ordering::Relaxed);
+            errors.get(attempt).cloned().map_or_else(|| Ok(encode::<Pot>(&())), Err)
+        });
+
+        (Fluxion::new("a", Palantir::new("a".to_string(), backend).unwrap()), sent)
+    }
+
+    fn immediate(retry_on: RetryOn) -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            retry_on,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[actor]
+    struct Receiver;
+
+    #[message]
+    #[derive(Serialize, Deserialize)]
+    struct Ping;
+
+    impl Handler<Ping> for Receiver {
+        async fn handle_message<D: Delegate>(&self, _message: Ping, _context: &ActorContext<D>) {}
+    }
+
+    #[tokio::test]
+    async fn interrupted_requests_are_retried() {
+        let (system, sent) = failing(vec![ChannelError::Interrupted, ChannelError::Interrupted]);
+        system.get_delegate().set_retry_policy(Some(immediate(RetryOn::Safe)));
+
+        let sender = system.get::<Receiver, Ping>(Identifier::Foreign(1, "b")).await.unwrap();
+        sender.send(Ping).await.unwrap();
+        assert_eq!(sent.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_are_limited() {
+        let (system, sent) = failing(vec![ChannelError::Interrupted; 5]);
+        system.get_delegate().set_retry_policy(Some(immediate(RetryOn::Safe)));
+
+        let sender = system.get::<Receiver, Ping>(Identifier::Foreign(1, "b")).await.unwrap();
+        let error = sender.send(Ping).await.unwrap_err();
+        assert!(matches!(PalantirSendError::of(&error), Some(PalantirSendError::Transport(ChannelError::Interrupted))));
+        assert_eq!(sent.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn only_policy_failures_are_retried() {
+        // Without a policy nothing is retried
+        let (system, sent) = failing(vec![ChannelError::Interrupted]);
+        let sender = system.get::<Receiver, Ping>(Identifier::Foreign(1, "b")).await.unwrap();
+        sender.send(Ping).await.unwrap_err();
+        assert_eq!(sent.load(Ordering::Relaxed), 1);
+
+        // The actor may have handled the message after a disconnect, so that is only retried if allowed
+        let (system, sent) = failing(vec![ChannelError::PeerDisconnected]);
+        system.get_delegate().set_retry_policy(Some(immediate(RetryOn::Safe)));
+        let sender = system.get::<Receiver, Ping>(Identifier::Foreign(1, "b")).await.unwrap();
+        sender.send(Ping).await.unwrap_err();
+        assert_eq!(sent.load(Ordering::Relaxed), 1);
+
+        let (system, sent) = failing(vec![ChannelError::PeerDisconnected]);
+        system.get_delegate().set_retry_policy(Some(immediate(RetryOn::Transient)));
+        let sender = system.get::<Receiver, Ping>(Identifier::Foreign(1, "b")).await.unwrap();
+        sender.send(Ping).await.unwrap();
+        assert_eq!(sent.load(Ordering::Relaxed), 2);
+
+        // Failures of the handler itself are never retried
+        let (system, sent) = failing(vec![ChannelError::RemoteHandler]);
+        system.get_delegate().set_retry_policy(Some(immediate(RetryOn::Transient)));
+        let sender = system.get::<Receiver, Ping>(Identifier::Foreign(1, "b")).await.unwrap();
+        sender.send(Ping).await.unwrap_err();
+        assert_eq!(sent.load(Ordering::Relaxed), 1);
+    }
+}
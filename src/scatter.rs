@@ -0,0 +1,153 @@
+//! # Scatter-gather
+//! [`Palantir::scatter_gather`](crate::Palantir::scatter_gather) sends the same request to many actors at once,
+//! and gathers their responses until a [`Quorum`] of them succeeded or a deadline passes,
+//! which is the usual way of talking to replicated actors.
+
+use fluxion::MessageSendError;
+
+use crate::ActorID;
+
+
+
+/// # [`Quorum`]
+/// How many targets of a scatter-gather request have to respond successfully for it to succeed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quorum {
+    /// # [`Quorum::First`]
+    /// The first given number of successful responses are enough.
+    First(usize),
+    /// # [`Quorum::Majority`]
+    /// More than half of the targets have to respond successfully.
+    Majority,
+    /// # [`Quorum::All`]
+    /// Every target has to respond successfully.
+    All,
+}
+
+impl Quorum {
+    /// # [`Quorum::required`]
+    /// Returns how many successful responses are required out of the given number of targets.
+    #[must_use]
+    pub fn required(self, targets: usize) -> usize {
+        match self {
+            Self::First(n) => n,
+            Self::Majority => targets / 2 + 1,
+            Self::All => targets,
+        }
+    }
+}
+
+/// # [`Gathered`]
+/// The outcome of a scatter-gather request.
+pub struct Gathered<R> {
+    /// Whether the quorum was reached
+    pub reached: bool,
+    /// The responses that arrived, as (system, actor, response), in the order they arrived
+    pub responses: Vec<(String, ActorID, Result<R, MessageSendError>)>,
+    /// The targets that hadn't responded when gathering stopped, as (system, actor)
+    pub pending: Vec<(String, ActorID)>,
+}
+
+impl<R> Gathered<R> {
+    /// # [`Gathered::successes`]
+    /// Returns the successful responses, in the order they arrived.
+    pub fn successes(&self) -> impl Iterator<Item = &R> {
+        self.responses.iter().filter_map(|(_, _, response)| response.as_ref().ok())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use fluxion::{actor, message, ActorContext, Delegate, Fluxion, Handler};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{backend::memory::MemoryBackend, testkit::{two_systems, ShutdownGuard}, Palantir, PalantirSendError};
+    use super::*;
+
+    /// Responds with its delay after waiting for it
+    #[actor]
+    struct Replica(u64);
+
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Read;
+
+    impl Handler<Read> for Replica {
+        async fn handle_message<D: Delegate>(&self, _message: Read, _context: &ActorContext<D>) -> u64 {
+            tokio::time::sleep(Duration::from_millis(self.0)).await;
+            self.0
+        }
+    }
+
+    /// Starts replicas with the given delays in milliseconds on `b`, and returns a system to send from alongside their targets
+    async fn replicas(delays: &[u64]) -> (ShutdownGuard, Fluxion<Palantir<MemoryBackend>>, Vec<(String, ActorID)>) {
+        let (a, b, guard) = two_systems("a", "b");
+
+        let mut targets = Vec::new();
+        for delay in delays {
+            let id = b.add(Replica(*delay)).await.unwrap();
+            b.get_delegate().register::<Replica, Read, _>(b.get_local::<Replica>(id).await.unwrap()).await;
+            targets.push(("b".to_string(), ActorID::Numeric(id)));
+        }
+
+        (guard, a, targets)
+    }
+
+    #[test]
+    fn required() {
+        assert_eq!(Quorum::First(2).required(5), 2);
+        assert_eq!(Quorum::Majority.required(4), 3);
+        assert_eq!(Quorum::Majority.required(5), 3);
+        assert_eq!(Quorum::All.required(5), 5);
+    }
+
+    #[tokio::test]
+    async fn quorum_reached() {
+        let (_guard, a, targets) = replicas(&[1, 1, 1000]).await;
+
+        let start = Instant::now();
+        let gathered = a.get_delegate().scatter_gather(targets.clone(), Read, Quorum::Majority, Duration::from_secs(5)).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // The slow replica is left pending
+        assert!(gathered.reached);
+        assert_eq!(gathered.successes().copied().collect::<Vec<_>>(), [1, 1]);
+        assert_eq!(gathered.pending, [targets[2].clone()]);
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let (_guard, a, targets) = replicas(&[1, 1000, 1000]).await;
+
+        let start = Instant::now();
+        let gathered = a.get_delegate().scatter_gather(targets.clone(), Read, Quorum::All, Duration::from_millis(50)).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // The partial results are kept
+        assert!(!gathered.reached);
+        assert_eq!(gathered.responses.len(), 1);
+        assert_eq!(gathered.responses[0].0, targets[0].0);
+        assert_eq!(gathered.responses[0].1, targets[0].1);
+        assert_eq!(gathered.pending, targets[1..]);
+    }
+
+    #[tokio::test]
+    async fn quorum_unreachable() {
+        let (_guard, a, mut targets) = replicas(&[1000]).await;
+        targets.push(("b".to_string(), ActorID::Named("missing".to_string())));
+
+        // Once the missing replica fails every replica can't succeed, so gathering stops without waiting for the slow one
+        let start = Instant::now();
+        let gathered = a.get_delegate().scatter_gather(targets.clone(), Read, Quorum::All, Duration::from_secs(5)).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        assert!(!gathered.reached);
+        assert_eq!(gathered.responses.len(), 1);
+        assert!(matches!(PalantirSendError::of(gathered.responses[0].2.as_ref().unwrap_err()), Some(PalantirSendError::ActorNotFound)));
+        assert_eq!(gathered.pending, [targets[0].clone()]);
+    }
+}
@@ -0,0 +1,147 @@
+//! # Schema
+//! Two systems only understand each other's messages if they agree on their shape, and a message whose shape changed
+//! between versions usually fails to deserialize once it arrives. When enabled with
+//! [`Palantir::set_schema_validation`](crate::Palantir::set_schema_validation), senders check this up front instead:
+//! every registration has a fingerprint of its message type, and of its result, and senders compare their own fingerprint
+//! of the message type with the registration's when they open a channel. A mismatch fails the send with
+//! [`PalantirSendError::SchemaMismatch`](crate::PalantirSendError::SchemaMismatch).
+//!
+//! Fingerprints are computed by tracing the types' serde implementations, so types whose formats can't be traced
+//! (e.g. ones that deserialize themselves with `deserialize_any`) have no fingerprint, and aren't checked.
+
+use fluxion::{IndeterminateMessage, Message, MessageID};
+use serde::{Deserialize, Serialize};
+use serde_reflection::{FormatHolder, Tracer, TracerConfig};
+
+
+
+/// # [`Handshake`]
+/// Asks for the fingerprint of the registration of the given message type on the actor the request is addressed to.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Handshake {
+    /// The message type whose fingerprint should be returned
+    pub message_type: String,
+}
+
+impl Message for Handshake {
+    /// The registration's fingerprint if it has one, still serialized
+    type Result = Vec<u8>;
+}
+
+impl MessageID for Handshake {
+    const ID: &'static str = "palantir::schema::Handshake";
+}
+
+/// # [`fingerprint`]
+/// Computes the fingerprint of the message type `M` and its result, or returns [`None`] if their formats can't be traced.
+pub(crate) fn fingerprint<M: IndeterminateMessage>() -> Option<u64>
+    where M::Result: Serialize + for<'de> Deserialize<'de> {
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (mut message, _) = tracer.trace_simple_type::<M>().ok()?;
+    let (mut result, _) = tracer.trace_simple_type::<M::Result>().ok()?;
+    message.normalize().ok()?;
+    result.normalize().ok()?;
+    let registry = tracer.registry().ok()?;
+
+    // The formats are hashed in their serialized form, as that is stable across builds and platforms
+    let formats = pot::to_vec(&(M::ID, message, result, registry)).ok()?;
+
+    Some(fnv1a(&formats))
+}
+
+/// # [`fnv1a`]
+/// Hashes the given data with 64 bit FNV-1a, which, unlike the standard library's hashers, is stable across Rust versions.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, ActorContext, Delegate, Handler, Identifier};
+
+    use crate::{testkit::two_systems, PalantirSendError};
+    use super::*;
+
+    /// Defines a `Put` message with the given field under the same id, as two versions of an application would
+    macro_rules! put {
+        ($version:ident, $field:ty) => {
+            mod $version {
+                use super::*;
+
+                #[derive(Serialize, Deserialize)]
+                pub struct Put(pub $field);
+
+                impl Message for Put {
+                    type Result = bool;
+                }
+
+                impl MessageID for Put {
+                    const ID: &'static str = "palantir::schema::tests::Put";
+                }
+            }
+        };
+    }
+
+    put!(v1, u64);
+    put!(v2, String);
+
+    #[actor]
+    struct Store;
+
+    impl Handler<v1::Put> for Store {
+        async fn handle_message<D: Delegate>(&self, _message: v1::Put, _context: &ActorContext<D>) -> bool {
+            true
+        }
+    }
+
+    impl Handler<v2::Put> for Store {
+        async fn handle_message<D: Delegate>(&self, _message: v2::Put, _context: &ActorContext<D>) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn fingerprints() {
+        assert_eq!(fingerprint::<v1::Put>(), fingerprint::<v1::Put>());
+        assert!(fingerprint::<v1::Put>().is_some());
+        assert_ne!(fingerprint::<v1::Put>(), fingerprint::<v2::Put>());
+    }
+
+    #[tokio::test]
+    async fn mismatch() {
+        let (a, b, _guard) = two_systems("a", "b");
+        a.get_delegate().set_schema_validation(true);
+
+        let id = b.add(Store).await.unwrap();
+        b.get_delegate().register::<Store, v1::Put, _>(b.get_local::<Store>(id).await.unwrap()).await;
+
+        // The same version is sent
+        let v1 = a.get::<Store, v1::Put>(Identifier::Foreign(id, "b")).await.unwrap();
+        assert!(v1.send(v1::Put(1)).await.unwrap());
+
+        // The other version is rejected before it is sent
+        let v2 = a.get::<Store, v2::Put>(Identifier::Foreign(id, "b")).await.unwrap();
+        let error = v2.send(v2::Put("one".to_string())).await.unwrap_err();
+        let Some(PalantirSendError::SchemaMismatch { message_type, local, remote }) = PalantirSendError::of(&error) else {
+            panic!("expected a schema mismatch, got {error}");
+        };
+        assert_eq!(*message_type, v2::Put::ID);
+        assert_eq!(Some(*local), fingerprint::<v2::Put>());
+        assert_eq!(Some(*remote), fingerprint::<v1::Put>());
+    }
+
+    #[tokio::test]
+    async fn mismatch_unchecked() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        let id = b.add(Store).await.unwrap();
+        b.get_delegate().register::<Store, v1::Put, _>(b.get_local::<Store>(id).await.unwrap()).await;
+
+        // Without validation, the other version is sent, and fails to deserialize
+        let v2 = a.get::<Store, v2::Put>(Identifier::Foreign(id, "b")).await.unwrap();
+        assert!(v2.send(v2::Put("one".to_string())).await.is_err());
+    }
+}
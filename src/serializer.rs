@@ -0,0 +1,226 @@
+//! # Serializer
+//! Contains the [`Serializer`] trait, which decides the wire format of messages and their responses.
+//! [`Palantir`](crate::Palantir) uses [`Pot`] by default, and [`Postcard`], [`Bincode`], and [`Json`] are available
+//! with the features of the same (lowercase) names. Both ends of a channel have to use the same serializer.
+//!
+//! Palantir's own envelopes, such as forwarded or keyed requests, always use pot, and carry the messages inside them
+//! in the serializer's format.
+
+use serde::{Deserialize, Serialize};
+
+
+
+/// # [`Serializer`]
+/// A wire format for messages and their responses.
+pub trait Serializer: Send + Sync + 'static {
+    /// # [`Serializer::Error`]
+    /// The error returned when a value can't be serialized or deserialized.
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// # [`Serializer::serialize`]
+    /// Serializes a value.
+    ///
+    /// # Errors
+    /// Returns an error if the value can't be represented in this format.
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    /// # [`Serializer::deserialize`]
+    /// Deserializes a value.
+    ///
+    /// # Errors
+    /// Returns an error if the data isn't a valid `T` in this format.
+    fn deserialize<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// # [`Pot`]
+/// Serializes with [`pot`], which is palantir's default.
+pub struct Pot;
+
+impl Serializer for Pot {
+    type Error = pot::Error;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        pot::to_vec(value)
+    }
+
+    fn deserialize<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, Self::Error> {
+        pot::from_slice(data)
+    }
+}
+
+/// # [`Postcard`]
+/// Serializes with [`postcard`], a compact format that isn't self-describing.
+#[cfg(feature = "postcard")]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Serializer for Postcard {
+    type Error = postcard::Error;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        postcard::to_allocvec(value)
+    }
+
+    fn deserialize<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, Self::Error> {
+        postcard::from_bytes(data)
+    }
+}
+
+/// # [`Bincode`]
+/// Serializes with [`bincode`].
+#[cfg(feature = "bincode")]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Serializer for Bincode {
+    type Error = bincode::Error;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        bincode::serialize(value)
+    }
+
+    fn deserialize<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, Self::Error> {
+        bincode::deserialize(data)
+    }
+}
+
+/// # [`Json`]
+/// Serializes with [`serde_json`], which is the easiest format for other languages to interoperate with.
+#[cfg(feature = "json")]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Serializer for Json {
+    type Error = serde_json::Error;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn deserialize<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(data)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, message, ActorContext, Delegate, Fluxion, Handler, Identifier, MessageID};
+
+    use crate::{backend::memory::MemoryNetwork, testkit::RecordingBackend, Palantir};
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Circle(f64),
+        Rectangle { width: u32, height: u32 },
+        Empty,
+    }
+
+    /// Covers the kinds of values messages are usually made of
+    #[message(Vec<Shape>)]
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Draw {
+        name: String,
+        shapes: Vec<Shape>,
+        layer: Option<i64>,
+        visible: bool,
+    }
+
+    fn draw() -> Draw {
+        Draw {
+            name: "canvas".to_string(),
+            shapes: vec![Shape::Circle(1.5), Shape::Rectangle { width: 3, height: 4 }, Shape::Empty],
+            layer: Some(-2),
+            visible: true,
+        }
+    }
+
+    #[actor]
+    struct Canvas;
+
+    impl Handler<Draw> for Canvas {
+        async fn handle_message<D: Delegate>(&self, message: Draw, _context: &ActorContext<D>) -> Vec<Shape> {
+            message.shapes.into_iter().rev().collect()
+        }
+    }
+
+    fn round_trip<S: Serializer>() {
+        let data = S::serialize(&draw()).unwrap();
+        assert_eq!(S::deserialize::<Draw>(&data).unwrap(), draw());
+        assert!(S::deserialize::<Draw>(&data[..data.len() / 2]).is_err());
+    }
+
+    /// Sends a message between two systems that both serialize with `S`, and checks that it was sent in that format
+    async fn end_to_end<S: Serializer>() {
+        let network = MemoryNetwork::new();
+        let a = Fluxion::new("a", Palantir::<_, S>::with_serializer("a".to_string(), RecordingBackend::new(network.backend("a"))).unwrap());
+        let b = Fluxion::new("b", Palantir::<_, S>::with_serializer("b".to_string(), network.backend("b")).unwrap());
+        let serving = tokio::spawn({
+            let b = b.clone();
+            async move { b.get_delegate().serve().await }
+        });
+
+        let id = b.add(Canvas).await.unwrap();
+        b.get_delegate().register::<Canvas, Draw, _>(b.get_local::<Canvas>(id).await.unwrap()).await;
+
+        let sender = a.get::<Canvas, Draw>(Identifier::Foreign(id, "b")).await.unwrap();
+        let reversed = sender.send(draw()).await.unwrap();
+        assert_eq!(reversed, draw().shapes.into_iter().rev().collect::<Vec<_>>());
+
+        let captured = a.get_delegate().backend.captured();
+        let request = captured.iter().find(|captured| captured.message_type == Draw::ID).unwrap();
+        assert_eq!(S::deserialize::<Draw>(&request.data).unwrap(), draw());
+        assert_eq!(S::deserialize::<Vec<Shape>>(request.response.as_ref().unwrap().as_ref().unwrap()).unwrap(), reversed);
+
+        serving.abort();
+        let _ = serving.await;
+    }
+
+    #[test]
+    fn pot_round_trip() {
+        round_trip::<Pot>();
+    }
+
+    #[tokio::test]
+    async fn pot_end_to_end() {
+        end_to_end::<Pot>().await;
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn postcard_round_trip() {
+        round_trip::<Postcard>();
+    }
+
+    #[cfg(feature = "postcard")]
+    #[tokio::test]
+    async fn postcard_end_to_end() {
+        end_to_end::<Postcard>().await;
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trip() {
+        round_trip::<Bincode>();
+    }
+
+    #[cfg(feature = "bincode")]
+    #[tokio::test]
+    async fn bincode_end_to_end() {
+        end_to_end::<Bincode>().await;
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        round_trip::<Json>();
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_end_to_end() {
+        end_to_end::<Json>().await;
+    }
+}
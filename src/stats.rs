@@ -0,0 +1,381 @@
+//! # Stats
+//! Palantir tracks statistics for every registration, keyed by the registered actor and the message type it handles.
+//! These can be retrieved with [`Palantir::stats`](crate::Palantir::stats) to find hot or slow actors, and in more detail,
+//! including why messages failed and a histogram of their latencies, with [`Palantir::metrics`](crate::Palantir::metrics).
+
+use std::{collections::VecDeque, sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Mutex}, time::Duration};
+
+
+
+/// # [`LATENCY_SAMPLES`]
+/// How many of the most recent handler latencies are kept to compute percentiles from.
+pub const LATENCY_SAMPLES: usize = 1024;
+
+/// # [`LATENCY_BUCKETS`]
+/// The upper bounds of the buckets of [`LatencyHistogram`]s.
+pub const LATENCY_BUCKETS: [Duration; 12] = [
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// # [`ActorStats`]
+/// A snapshot of the statistics for a single actor and message type.
+#[derive(Clone, Debug, Default)]
+pub struct ActorStats {
+    /// How many messages have been handled, including failures.
+    pub handled: u64,
+    /// How many of the handled messages failed, either because they couldn't be deserialized,
+    /// the actor failed to handle them, or the response couldn't be serialized.
+    pub failures: u64,
+    /// How many messages are currently waiting to be handled.
+    pub queue_depth: usize,
+    /// How many messages are currently being handled.
+    pub handling: usize,
+    /// How many times the handler panicked while handling a message.
+    pub panics: usize,
+    /// The mean time taken to handle a message.
+    pub mean_latency: Duration,
+    /// The median time taken to handle one of the last [`LATENCY_SAMPLES`] messages.
+    pub p50_latency: Duration,
+    /// The 99th percentile time taken to handle one of the last [`LATENCY_SAMPLES`] messages.
+    pub p99_latency: Duration,
+}
+
+/// # [`LatencyHistogram`]
+/// How many messages were handled within each of the [`LATENCY_BUCKETS`].
+#[derive(Clone, Debug, Default)]
+pub struct LatencyHistogram {
+    /// The upper bound of each bucket alongside how many messages took longer than the previous bucket's bound, but no longer than it.
+    pub buckets: Vec<(Duration, u64)>,
+    /// How many messages took longer than the last bucket's bound.
+    pub overflow: u64,
+    /// The total time taken to handle every message.
+    pub sum: Duration,
+}
+
+/// # [`ActorMetrics`]
+/// A snapshot of the counters and latency histogram for a single actor and message type.
+#[derive(Clone, Debug, Default)]
+pub struct ActorMetrics {
+    /// How many requests were received for the actor, including ones that were rejected because its queue was full.
+    pub received: u64,
+    /// How many messages have been handled, including failures.
+    pub handled: u64,
+    /// How many messages couldn't be deserialized.
+    pub deserialization_failures: u64,
+    /// How many messages the actor failed to handle, including ones it panicked while handling.
+    pub handler_errors: u64,
+    /// How many responses couldn't be serialized.
+    pub serialization_failures: u64,
+    /// How long handled messages took, including failures.
+    pub latency: LatencyHistogram,
+}
+
+/// # [`Outcome`]
+/// How handling a message ended.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// The message was handled, and its response was serialized
+    Succeeded,
+    /// The message couldn't be deserialized
+    DeserializationFailed,
+    /// The actor failed to handle the message
+    HandlerFailed,
+    /// The response couldn't be serialized
+    SerializationFailed,
+}
+
+/// # [`Tracker`]
+/// Records the statistics for a single actor and message type as messages are handled.
+#[derive(Default)]
+pub(crate) struct Tracker {
+    /// How many requests were received
+    received: AtomicU64,
+    /// How many messages are currently queued
+    queued: AtomicUsize,
+    /// How many messages are currently being handled
+    handling: AtomicUsize,
+    /// How many times the handler panicked
+    panics: AtomicUsize,
+    /// Everything else, which is updated once per handled message
+    handled: Mutex<Handled>,
+}
+
+/// # [`Handled`]
+/// The statistics a [`Tracker`] records for handled messages.
+#[derive(Default)]
+struct Handled {
+    /// How many messages have been handled
+    count: u64,
+    /// How many messages failed
+    failures: u64,
+    /// How many messages couldn't be deserialized
+    deserialization_failures: u64,
+    /// How many messages the actor failed to handle
+    handler_errors: u64,
+    /// How many responses couldn't be serialized
+    serialization_failures: u64,
+    /// How many messages fell into each of the [`LATENCY_BUCKETS`], followed by how many took longer than all of them
+    histogram: [u64; LATENCY_BUCKETS.len() + 1],
+    /// The total time spent handling messages
+    total_latency: Duration,
+    /// The latencies of the most recent messages, oldest first
+    recent: VecDeque<Duration>,
+}
+
+impl Tracker {
+    /// # [`Tracker::enqueued`]
+    /// Records that a request was received, and queued to be handled.
+    pub fn enqueued(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # [`Tracker::dequeued`]
+    /// Records that a queued message was removed from the queue.
+    pub fn dequeued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// # [`Tracker::started`]
+    /// Records that a dequeued message started being handled. It is handled once it is passed to [`Tracker::record`].
+    pub fn started(&self) {
+        self.handling.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # [`Tracker::panicked`]
+    /// Records that the handler panicked, returning how many times it has panicked now.
+    pub fn panicked(&self) -> usize {
+        self.panics.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// # [`Tracker::in_flight`]
+    /// Returns how many messages are either queued or being handled.
+    pub fn in_flight(&self) -> usize {
+        self.queued.load(Ordering::Relaxed) + self.handling.load(Ordering::Relaxed)
+    }
+
+    /// # [`Tracker::record`]
+    /// Records that a message was handled in the given time, with the given outcome.
+    pub fn record(&self, latency: Duration, outcome: Outcome) {
+        self.handling.fetch_sub(1, Ordering::Relaxed);
+
+        let mut handled = self.handled.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        handled.count += 1;
+        match outcome {
+            Outcome::Succeeded => {},
+            Outcome::DeserializationFailed => handled.deserialization_failures += 1,
+            Outcome::HandlerFailed => handled.handler_errors += 1,
+            Outcome::SerializationFailed => handled.serialization_failures += 1,
+        }
+        if outcome != Outcome::Succeeded {
+            handled.failures += 1;
+        }
+        handled.total_latency += latency;
+
+        let bucket = LATENCY_BUCKETS.iter().position(|bound| latency <= *bound).unwrap_or(LATENCY_BUCKETS.len());
+        handled.histogram[bucket] += 1;
+
+        if handled.recent.len() == LATENCY_SAMPLES {
+            handled.recent.pop_front();
+        }
+        handled.recent.push_back(latency);
+    }
+
+    /// # [`Tracker::snapshot`]
+    /// Returns the current statistics.
+    pub fn snapshot(&self) -> ActorStats {
+        let handled = self.handled.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        let mut recent = handled.recent.iter().copied().collect::<Vec<_>>();
+        recent.sort_unstable();
+        let percentile = |p: usize| recent.get((recent.len().saturating_sub(1)) * p / 100).copied().unwrap_or_default();
+
+        let mean_latency = if handled.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos(u64::try_from(handled.total_latency.as_nanos() / u128::from(handled.count)).unwrap_or(u64::MAX))
+        };
+
+        ActorStats {
+            handled: handled.count,
+            failures: handled.failures,
+            queue_depth: self.queued.load(Ordering::Relaxed),
+            handling: self.handling.load(Ordering::Relaxed),
+            panics: self.panics.load(Ordering::Relaxed),
+            mean_latency,
+            p50_latency: percentile(50),
+            p99_latency: percentile(99),
+        }
+    }
+
+    /// # [`Tracker::metrics`]
+    /// Returns the current counters and latency histogram.
+    pub fn metrics(&self) -> ActorMetrics {
+        let handled = self.handled.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        ActorMetrics {
+            received: self.received.load(Ordering::Relaxed),
+            handled: handled.count,
+            deserialization_failures: handled.deserialization_failures,
+            handler_errors: handled.handler_errors,
+            serialization_failures: handled.serialization_failures,
+            latency: LatencyHistogram {
+                buckets: LATENCY_BUCKETS.iter().copied().zip(handled.histogram).collect(),
+                overflow: handled.histogram[LATENCY_BUCKETS.len()],
+                sum: handled.total_latency,
+            },
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Passes a message through the tracker from being queued to being handled in the given time
+    fn handle(tracker: &Tracker, latency: Duration, outcome: Outcome) {
+        tracker.enqueued();
+        tracker.dequeued();
+        tracker.started();
+        tracker.record(latency, outcome);
+    }
+
+    #[test]
+    fn nothing_handled() {
+        let stats = Tracker::default().snapshot();
+
+        assert_eq!((stats.handled, stats.failures), (0, 0));
+        assert_eq!(stats.mean_latency, Duration::ZERO);
+        assert_eq!(stats.p50_latency, Duration::ZERO);
+        assert_eq!(stats.p99_latency, Duration::ZERO);
+    }
+
+    #[test]
+    fn in_flight() {
+        let tracker = Tracker::default();
+
+        tracker.enqueued();
+        tracker.enqueued();
+        tracker.dequeued();
+        tracker.started();
+        let stats = tracker.snapshot();
+        assert_eq!((stats.queue_depth, stats.handling), (1, 1));
+        assert_eq!(tracker.in_flight(), 2);
+
+        tracker.record(Duration::from_millis(1), Outcome::Succeeded);
+        assert_eq!(tracker.in_flight(), 1);
+        assert_eq!(tracker.panicked(), 1);
+        assert_eq!(tracker.panicked(), 2);
+        assert_eq!(tracker.snapshot().panics, 2);
+    }
+
+    #[test]
+    fn latencies() {
+        let tracker = Tracker::default();
+
+        // Recorded out of order, so that the percentiles have to sort them
+        for millis in (1..=100).rev() {
+            let outcome = if millis % 10 == 0 { Outcome::HandlerFailed } else { Outcome::Succeeded };
+            handle(&tracker, Duration::from_millis(millis), outcome);
+        }
+
+        let stats = tracker.snapshot();
+        assert_eq!((stats.handled, stats.failures), (100, 10));
+        assert_eq!(stats.mean_latency, Duration::from_micros(50_500));
+        assert_eq!(stats.p50_latency, Duration::from_millis(50));
+        assert_eq!(stats.p99_latency, Duration::from_millis(99));
+    }
+
+    #[test]
+    fn single_latency() {
+        let tracker = Tracker::default();
+        handle(&tracker, Duration::from_millis(7), Outcome::Succeeded);
+
+        let stats = tracker.snapshot();
+        assert_eq!(stats.mean_latency, Duration::from_millis(7));
+        assert_eq!(stats.p50_latency, Duration::from_millis(7));
+        assert_eq!(stats.p99_latency, Duration::from_millis(7));
+    }
+
+    #[test]
+    fn recent_latencies() {
+        let tracker = Tracker::default();
+
+        // Only the most recent samples count towards the percentiles, but every message counts towards the mean
+        for _ in 0..LATENCY_SAMPLES {
+            handle(&tracker, Duration::from_secs(1), Outcome::Succeeded);
+        }
+        for _ in 0..LATENCY_SAMPLES {
+            handle(&tracker, Duration::from_millis(1), Outcome::Succeeded);
+        }
+
+        let stats = tracker.snapshot();
+        assert_eq!(stats.p99_latency, Duration::from_millis(1));
+        assert_eq!(stats.mean_latency, Duration::from_micros(500_500));
+    }
+
+    #[test]
+    fn histogram() {
+        let tracker = Tracker::default();
+
+        // Bounds are inclusive, and anything past the last bound overflows
+        for latency in [Duration::ZERO, Duration::from_millis(1), Duration::from_micros(1001), Duration::from_millis(10),
+            Duration::from_secs(10), Duration::from_secs(11), Duration::from_secs(60)] {
+            handle(&tracker, latency, Outcome::Succeeded);
+        }
+
+        let latency = tracker.metrics().latency;
+        assert_eq!(latency.buckets.len(), LATENCY_BUCKETS.len());
+        assert_eq!(latency.buckets.iter().map(|(bound, _)| *bound).collect::<Vec<_>>(), LATENCY_BUCKETS);
+
+        let counts = latency.buckets.iter().map(|(_, count)| *count).collect::<Vec<_>>();
+        assert_eq!(counts, [2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(latency.overflow, 2);
+        assert_eq!(latency.sum, Duration::from_micros(81_012_001));
+    }
+
+    #[test]
+    fn outcomes() {
+        let tracker = Tracker::default();
+
+        handle(&tracker, Duration::ZERO, Outcome::Succeeded);
+        handle(&tracker, Duration::ZERO, Outcome::DeserializationFailed);
+        handle(&tracker, Duration::ZERO, Outcome::HandlerFailed);
+        handle(&tracker, Duration::ZERO, Outcome::HandlerFailed);
+        handle(&tracker, Duration::ZERO, Outcome::SerializationFailed);
+
+        // Requests rejected because the queue is full are still received
+        tracker.enqueued();
+        tracker.dequeued();
+
+        let metrics = tracker.metrics();
+        assert_eq!((metrics.received, metrics.handled), (6, 5));
+        assert_eq!(metrics.deserialization_failures, 1);
+        assert_eq!(metrics.handler_errors, 2);
+        assert_eq!(metrics.serialization_failures, 1);
+        assert_eq!(tracker.snapshot().failures, 4);
+    }
+
+    #[test]
+    fn empty_histogram() {
+        let latency = Tracker::default().metrics().latency;
+
+        assert!(latency.buckets.iter().all(|(_, count)| *count == 0));
+        assert_eq!(latency.overflow, 0);
+        assert_eq!(latency.sum, Duration::ZERO);
+    }
+}
@@ -0,0 +1,371 @@
+//! # Supervision
+//! Handlers that panic while handling a message are caught, and the request is responded to with
+//! [`ChannelError::RemoteHandler`]. What happens to the registration afterwards
+//! is decided by the instance's [`PanicPolicy`], set with [`Palantir::set_panic_policy`](crate::Palantir::set_panic_policy).
+//!
+//! The task relaying each actor's requests to its handlers is supervised as well, and restarted if it panics, so that the actor
+//! keeps being served. Applications can be notified of repeated [`Failure`]s of either kind with
+//! [`Palantir::on_repeated_failure`](crate::Palantir::on_repeated_failure).
+
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, PoisonError, RwLock};
+
+use tokio::{sync::{mpsc, watch, Semaphore}, task::JoinSet};
+
+use crate::{backend::ChannelError, debug, Queued};
+
+
+
+/// # [`PanicPolicy`]
+/// What happens to a registration whose handler panicked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// # [`PanicPolicy::Continue`]
+    /// The registration keeps handling messages.
+    #[default]
+    Continue,
+    /// # [`PanicPolicy::Pause`]
+    /// Once the handler has panicked the given number of times, the registration stops handling messages until it is
+    /// resumed with [`Palantir::resume`](crate::Palantir::resume).
+    Pause {
+        /// How many panics pause the registration
+        after: usize,
+    },
+    /// # [`PanicPolicy::Deregister`]
+    /// Once the handler has panicked the given number of times, the registration is removed like with
+    /// [`Palantir::unregister`](crate::Palantir::unregister), which publishes [`Event::RegistrationRemoved`](crate::Event::RegistrationRemoved).
+    Deregister {
+        /// How many panics remove the registration
+        after: usize,
+    },
+}
+
+/// # [`Control`]
+/// The state of a single registration, shared between its dispatcher and its relay task.
+/// Paused and removed registrations respond to requests with [`ChannelError::HandlerNotFound`](crate::backend::ChannelError::HandlerNotFound).
+#[derive(Default)]
+pub(crate) struct Control {
+    /// Whether the registration is paused
+    paused: AtomicBool,
+    /// Whether the registration was removed
+    removed: AtomicBool,
+}
+
+impl Control {
+    /// # [`Control::is_paused`]
+    /// Returns whether the registration is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// # [`Control::set_paused`]
+    /// Pauses or resumes the registration.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// # [`Control::is_removed`]
+    /// Returns whether the registration was removed.
+    pub fn is_removed(&self) -> bool {
+        self.removed.load(Ordering::Relaxed)
+    }
+
+    /// # [`Control::remove`]
+    /// Removes the registration.
+    pub fn remove(&self) {
+        self.removed.store(true, Ordering::Relaxed);
+    }
+
+    /// # [`Control::panicked`]
+    /// Applies the given policy to a registration whose handler has now panicked the given number of times,
+    /// pausing it if the policy says so. Returns whether the policy says to remove it, which the caller is responsible for.
+    pub fn panicked(&self, policy: PanicPolicy, panics: usize) -> bool {
+        match policy {
+            PanicPolicy::Pause { after } if panics >= after => {
+                self.set_paused(true);
+                false
+            },
+            PanicPolicy::Deregister { after } if panics >= after => true,
+            _ => false,
+        }
+    }
+}
+
+/// # [`Failure`]
+/// A repeated failure that the application is notified of.
+#[derive(Clone, Debug)]
+pub enum Failure {
+    /// # [`Failure::Handler`]
+    /// A local actor's handler panicked while handling a message.
+    Handler {
+        /// The local actor's id
+        actor: u64,
+        /// The message type being handled
+        message_type: &'static str,
+        /// How many times the actor has panicked while handling this message type
+        panics: usize,
+    },
+    /// # [`Failure::Relay`]
+    /// The task relaying requests to a local actor panicked, and was restarted.
+    Relay {
+        /// The local actor's id
+        actor: u64,
+        /// How many times the actor's relay task has been restarted
+        restarts: usize,
+    },
+}
+
+impl Failure {
+    /// # [`Failure::count`]
+    /// Returns how many times this has failed so far, including this failure.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        match self {
+            Self::Handler { panics, .. } => *panics,
+            Self::Relay { restarts, .. } => *restarts,
+        }
+    }
+}
+
+/// # [`Callback`]
+/// Notifies the application of a [`Failure`].
+type Callback = Arc<dyn Fn(&Failure) + Send + Sync>;
+
+/// # [`Supervisor`]
+/// Holds the callback that is notified of repeated failures, alongside how many failures it is notified after.
+#[derive(Default)]
+pub(crate) struct Supervisor {
+    /// The callback, if there is one
+    callback: RwLock<Option<(usize, Callback)>>,
+}
+
+impl Supervisor {
+    /// # [`Supervisor::set`]
+    /// Replaces the callback, or removes it if [`None`].
+    pub fn set(&self, callback: Option<(usize, Callback)>) {
+        *self.callback.write().unwrap_or_else(PoisonError::into_inner) = callback;
+    }
+
+    /// # [`Supervisor::report`]
+    /// Notifies the callback of the given failure, if it has happened often enough.
+    pub fn report(&self, failure: &Failure) {
+        // Don't hold the lock while calling back, so that the callback may replace itself
+        let callback = self.callback.read().unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .filter(|(after, _)| failure.count() >= *after)
+            .map(|(_, callback)| callback.clone());
+
+        if let Some(callback) = callback {
+            callback(failure);
+        }
+    }
+}
+
+/// # [`relay`]
+/// Takes the requests queued for the given actor and handles each in its own task on the given join set,
+/// waiting for a free slot before taking each one. Once the instance has left the cluster, the requests
+/// still queued are rejected with [`ChannelError::Leaving`] instead. Returns once there will never be any more requests.
+///
+/// # Panics
+/// Panics if the join set mutex is poisoned, which should never happen.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) async fn relay(actor: u64, receiver: Arc<tokio::sync::Mutex<mpsc::Receiver<Queued>>>, slots: Option<Arc<Semaphore>>, join_set: Arc<Mutex<JoinSet<()>>>, left: watch::Receiver<bool>) {
+    let mut receiver = receiver.lock().await;
+
+    // The main loop for receiving messages for this specific actor
+    loop {
+
+        // Wait for a free slot before taking the next message, so that it stays queued until then.
+        // The semaphore is never closed.
+        let slot = match &slots {
+            Some(slots) => slots.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+
+        // Receive the next message.
+        let Some(queued) = receiver.recv().await else {
+            // This point will only ever be reached if there are no longer
+            // any senders, which means there will never be any others.
+            // This doesn't necessarily mean that the palantir instance is broken,
+            // just that this actor's messages will never be received again.
+            debug!(actor, "relay stopped receiving requests");
+            break;
+        };
+        queued.stats.dequeued();
+
+        // A removed registration rejects everything that is still queued for it.
+        // Its sender is dropped once the registration is forgotten.
+        if queued.control.is_removed() {
+            let _ = queued.request.respond(Err(ChannelError::HandlerNotFound));
+            continue;
+        }
+        if *left.borrow() {
+            let _ = queued.request.respond(Err(ChannelError::Leaving));
+            continue;
+        }
+        queued.stats.started();
+
+        // Spawn a new task handling the message
+        join_set.lock().expect("join set mutex should never be poisoned")
+            .spawn(async move {
+                let _slot = slot;
+                (queued.handle)(queued.target, queued.request).await;
+            });
+
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use fluxion::{actor, message, ActorContext, Delegate, Fluxion, Handler, Identifier, MessageID};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{backend::memory::MemoryBackend, testkit::two_systems, Event, Palantir, PalantirSendError};
+    use super::*;
+
+    #[actor]
+    struct Fragile;
+
+    /// Panics if the divisor is zero
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Divide(u64);
+
+    impl Handler<Divide> for Fragile {
+        async fn handle_message<D: Delegate>(&self, message: Divide, _context: &ActorContext<D>) -> u64 {
+            assert!(message.0 != 0, "divided by zero");
+            100 / message.0
+        }
+    }
+
+    /// Registers a fragile actor on `b` under the given policy, and returns its id alongside a sender to it from `a`
+    async fn fragile(a: &Fluxion<Palantir<MemoryBackend>>, b: &Fluxion<Palantir<MemoryBackend>>, policy: PanicPolicy) -> (u64, std::sync::Arc<dyn fluxion::MessageSender<Divide>>) {
+        b.get_delegate().set_panic_policy(policy);
+        let id = b.add(Fragile).await.unwrap();
+        b.get_delegate().register::<Fragile, Divide, _>(b.get_local::<Fragile>(id).await.unwrap()).await;
+
+        (id, a.get::<Fragile, Divide>(Identifier::Foreign(id, "b")).await.unwrap())
+    }
+
+    fn panicked(error: &fluxion::MessageSendError) -> bool {
+        matches!(PalantirSendError::of(error), Some(PalantirSendError::RemoteHandler))
+    }
+
+    fn not_found(error: &fluxion::MessageSendError) -> bool {
+        matches!(PalantirSendError::of(error), Some(PalantirSendError::ActorNotFound))
+    }
+
+    #[tokio::test]
+    async fn panics_are_counted() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let mut events = b.get_delegate().events();
+        let (id, sender) = fragile(&a, &b, PanicPolicy::Continue).await;
+
+        // The panic is answered, and the registration keeps handling messages
+        assert!(panicked(&sender.send(Divide(0)).await.unwrap_err()));
+        assert_eq!(sender.send(Divide(4)).await.unwrap(), 25);
+        assert!(panicked(&sender.send(Divide(0)).await.unwrap_err()));
+
+        assert_eq!(b.get_delegate().stats()[&(id, Divide::ID.to_string())].panics, 2);
+
+        let mut panics = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::HandlerPanicked { actor, message_type, panics: count } = event {
+                assert_eq!((actor, message_type), (id, Divide::ID));
+                panics.push(count);
+            }
+        }
+        assert_eq!(panics, [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn pause() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let (id, sender) = fragile(&a, &b, PanicPolicy::Pause { after: 2 }).await;
+
+        assert!(panicked(&sender.send(Divide(0)).await.unwrap_err()));
+        assert_eq!(sender.send(Divide(4)).await.unwrap(), 25);
+        assert!(panicked(&sender.send(Divide(0)).await.unwrap_err()));
+
+        // Paused after the second panic, until it is resumed
+        assert!(not_found(&sender.send(Divide(4)).await.unwrap_err()));
+        assert!(b.get_delegate().resume(id, Divide::ID).await);
+        assert_eq!(sender.send(Divide(4)).await.unwrap(), 25);
+    }
+
+    #[tokio::test]
+    async fn deregister() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let mut events = b.get_delegate().events();
+        let (id, sender) = fragile(&a, &b, PanicPolicy::Deregister { after: 1 }).await;
+
+        assert!(panicked(&sender.send(Divide(0)).await.unwrap_err()));
+
+        // The registration is removed as soon as the handler panics
+        assert!(not_found(&sender.send(Divide(4)).await.unwrap_err()));
+        assert!(!b.get_delegate().resume(id, Divide::ID).await);
+
+        let removed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Event::RegistrationRemoved { actor, message_type } = events.recv().await.unwrap() {
+                    break (actor, message_type);
+                }
+            }
+        }).await.unwrap();
+        assert_eq!(removed, (id, Divide::ID));
+    }
+
+    #[tokio::test]
+    async fn relay_restarted() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let mut events = b.get_delegate().events();
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        b.get_delegate().on_repeated_failure(1, {
+            let failures = failures.clone();
+            move |failure| failures.lock().unwrap().push(failure.clone())
+        });
+        let (id, sender) = fragile(&a, &b, PanicPolicy::Continue).await;
+        assert_eq!(sender.send(Divide(4)).await.unwrap(), 25);
+
+        // Poisoning the handlers' join set makes the relay panic once it takes the next request, which is lost
+        let handlers = b.get_delegate().handlers.clone();
+        std::thread::spawn(move || {
+            let _handlers = handlers.lock().unwrap();
+            panic!("poisoning the handlers");
+        }).join().unwrap_err();
+        assert!(sender.send(Divide(4)).await.is_err());
+
+        let restarts = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Event::RelayRestarted { actor, restarts } = events.recv().await.unwrap() {
+                    break (actor, restarts);
+                }
+            }
+        }).await.unwrap();
+        assert_eq!(restarts, (id, 1));
+        assert!(matches!(failures.lock().unwrap()[..], [Failure::Relay { actor, restarts: 1 }] if actor == id));
+
+        // The restarted relay keeps receiving the actor's requests
+        b.get_delegate().handlers.clear_poison();
+        assert_eq!(sender.send(Divide(4)).await.unwrap(), 25);
+    }
+
+    #[test]
+    fn policies() {
+        let control = Control::default();
+
+        assert!(!control.panicked(PanicPolicy::Continue, 10));
+        assert!(!control.is_paused());
+
+        assert!(!control.panicked(PanicPolicy::Pause { after: 2 }, 1));
+        assert!(!control.is_paused());
+        assert!(!control.panicked(PanicPolicy::Pause { after: 2 }, 2));
+        assert!(control.is_paused());
+
+        assert!(!control.panicked(PanicPolicy::Deregister { after: 2 }, 1));
+        assert!(control.panicked(PanicPolicy::Deregister { after: 2 }, 2));
+    }
+}
@@ -0,0 +1,85 @@
+//! # System IDs
+//! Contains the rules that system ids (and federation zones, which are prefixes of system ids) must follow.
+//! System ids are used as map keys, routing targets, and potentially as hostnames by backends,
+//! so they are limited to something that is safe to use in all of those places.
+
+use thiserror::Error;
+
+
+
+/// # [`MAX_SYSTEM_ID_LENGTH`]
+/// The maximum length of a system id in bytes, which matches the maximum length of a DNS name.
+pub const MAX_SYSTEM_ID_LENGTH: usize = 253;
+
+/// # [`SystemIdError`]
+/// The ways in which a system id can be invalid.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SystemIdError {
+    /// # [`SystemIdError::Empty`]
+    /// The system id is empty.
+    #[error("system ids can not be empty")]
+    Empty,
+    /// # [`SystemIdError::TooLong`]
+    /// The system id is longer than [`MAX_SYSTEM_ID_LENGTH`].
+    #[error("system id is {0} bytes long, which is more than the maximum of {MAX_SYSTEM_ID_LENGTH}")]
+    TooLong(usize),
+    /// # [`SystemIdError::InvalidCharacter`]
+    /// The system id contains a character other than an ASCII letter, digit, `-`, `_`, or `.`.
+    #[error("system ids can not contain {0:?}")]
+    InvalidCharacter(char),
+    /// # [`SystemIdError::EmptyLevel`]
+    /// The system id starts or ends with a `.`, or contains two in a row.
+    #[error("system ids can not have empty levels")]
+    EmptyLevel,
+}
+
+/// # [`validate`]
+/// Checks that the given string is a valid system id.
+///
+/// # Errors
+/// Returns the first [`SystemIdError`] found in the system id.
+pub fn validate(system_id: &str) -> Result<(), SystemIdError> {
+
+    if system_id.is_empty() {
+        return Err(SystemIdError::Empty);
+    }
+
+    if system_id.len() > MAX_SYSTEM_ID_LENGTH {
+        return Err(SystemIdError::TooLong(system_id.len()));
+    }
+
+    if let Some(c) = system_id.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))) {
+        return Err(SystemIdError::InvalidCharacter(c));
+    }
+
+    if system_id.split('.').any(str::is_empty) {
+        return Err(SystemIdError::EmptyLevel);
+    }
+
+    Ok(())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid() {
+        assert_eq!(validate("node1"), Ok(()));
+        assert_eq!(validate("eu.west.node_1-a"), Ok(()));
+        assert_eq!(validate(&"a".repeat(MAX_SYSTEM_ID_LENGTH)), Ok(()));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(validate(""), Err(SystemIdError::Empty));
+        assert_eq!(validate(&"a".repeat(MAX_SYSTEM_ID_LENGTH + 1)), Err(SystemIdError::TooLong(MAX_SYSTEM_ID_LENGTH + 1)));
+        assert_eq!(validate("eu/west"), Err(SystemIdError::InvalidCharacter('/')));
+        assert_eq!(validate("nöde"), Err(SystemIdError::InvalidCharacter('ö')));
+        assert_eq!(validate(".eu"), Err(SystemIdError::EmptyLevel));
+        assert_eq!(validate("eu."), Err(SystemIdError::EmptyLevel));
+        assert_eq!(validate("eu..west"), Err(SystemIdError::EmptyLevel));
+    }
+}
@@ -0,0 +1,444 @@
+//! # Testkit
+//! Backends for testing code that uses palantir without a network, enabled with the `testkit` feature.
+//! [`MockBackend`] answers outbound requests with a scripted responder and lets tests inject inbound requests,
+//! and [`RecordingBackend`] wraps any backend and captures all of its outbound traffic for assertions.
+//! [`two_systems`] connects two full palantir instances to each other in-process, for end-to-end tests.
+
+use std::{collections::HashSet, sync::{Arc, Mutex, PoisonError}};
+
+use fluxion::{Fluxion, Message};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::{mpsc, oneshot}, task::JoinSet};
+
+use crate::{backend::{memory::{MemoryBackend, MemoryNetwork}, Backend, Channel, ChannelError, OpenChannelError, PeerEvent}, serializer::Serializer, ActorID, Palantir, Request};
+
+
+
+/// # [`encode`]
+/// Serializes a message or response the same way a palantir instance using the serializer `S` does on the wire.
+/// Palantir's own envelopes, such as forwarded or keyed requests, are always pot (see [`Pot`](crate::serializer::Pot)),
+/// and carry the message inside them in `S`'s format.
+///
+/// # Panics
+/// Panics if the value can't be serialized.
+#[must_use]
+pub fn encode<S: Serializer>(value: &impl Serialize) -> Vec<u8> {
+    S::serialize(value).expect("value should be serializable")
+}
+
+/// # [`decode`]
+/// Deserializes a message or response the same way a palantir instance using the serializer `S` does on the wire.
+/// Like with [`encode`], palantir's own envelopes are always pot.
+///
+/// # Panics
+/// Panics if the data isn't a valid `T`.
+#[must_use]
+pub fn decode<S: Serializer, T: for<'de> Deserialize<'de>>(data: &[u8]) -> T {
+    S::deserialize(data).expect("data should deserialize to the expected type")
+}
+
+/// # [`Responder`]
+/// Produces the response to an outbound request, given its system, actor, message type, and data.
+type Responder = dyn Fn(&str, &ActorID, &str, &[u8]) -> Result<Vec<u8>, ChannelError> + Send + Sync;
+
+/// # [`MockBackend`]
+/// A [`Backend`] which answers every outbound request with a scripted responder, and only receives the inbound
+/// requests that are injected with [`MockBackend::inject`].
+pub struct MockBackend {
+    /// Produces the responses to outbound requests
+    respond: Arc<Responder>,
+    /// The systems that channels can't currently be opened to
+    unreachable: Mutex<HashSet<String>>,
+    /// Sends injected inbound requests
+    inject: mpsc::UnboundedSender<(ActorID, String, Request)>,
+    /// Receives injected inbound requests
+    incoming: tokio::sync::Mutex<mpsc::UnboundedReceiver<(ActorID, String, Request)>>,
+}
+
+impl MockBackend {
+    /// # [`MockBackend::new`]
+    /// Creates a [`MockBackend`] which answers outbound requests with the given responder,
+    /// which receives the request's system, actor, message type, and data.
+    pub fn new(respond: impl Fn(&str, &ActorID, &str, &[u8]) -> Result<Vec<u8>, ChannelError> + Send + Sync + 'static) -> Self {
+        let (inject, incoming) = mpsc::unbounded_channel();
+
+        Self {
+            respond: Arc::new(respond),
+            unreachable: Mutex::default(),
+            inject,
+            incoming: tokio::sync::Mutex::new(incoming),
+        }
+    }
+
+    /// # [`MockBackend::set_unreachable`]
+    /// Sets whether opening channels to the given system fails with [`OpenChannelError::SystemUnreachable`].
+    pub fn set_unreachable(&self, system: &str, unreachable: bool) {
+        let mut systems = self.unreachable.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if unreachable {
+            systems.insert(system.to_string());
+        } else {
+            systems.remove(system);
+        }
+    }
+
+    /// # [`MockBackend::inject`]
+    /// Injects an inbound request to the given actor and message type, which is received from [`Backend::incoming`],
+    /// and returns the receiver of its response.
+    pub fn inject(&self, actor: ActorID, message_type: &str, data: Vec<u8>) -> oneshot::Receiver<Result<Vec<u8>, ChannelError>> {
+        let (request, response) = Request::new(data);
+        self.inject_request(actor, message_type, request);
+        response
+    }
+
+    /// # [`MockBackend::inject_request`]
+    /// Injects the given inbound request to the given actor and message type, like [`MockBackend::inject`].
+    /// This can inject requests with ids (see [`Request::with_id`]).
+    pub fn inject_request(&self, actor: ActorID, message_type: &str, request: Request) {
+        // The receiver lives as long as the backend, so this can't fail
+        let _ = self.inject.send((actor, message_type.to_string(), request));
+    }
+}
+
+impl Backend for MockBackend {
+    type Channel = MockChannel;
+
+    async fn open_channel<M: Message>(&self, actor: ActorID, system: &str, message_type: &str) -> Result<Self::Channel, OpenChannelError> {
+
+        if self.unreachable.lock().unwrap_or_else(PoisonError::into_inner).contains(system) {
+            return Err(OpenChannelError::SystemUnreachable(system.to_string()));
+        }
+
+        Ok(MockChannel {
+            respond: self.respond.clone(),
+            system: system.to_string(),
+            actor,
+            message_type: message_type.to_string(),
+        })
+    }
+
+    async fn incoming(&self) -> Option<(ActorID, String, Request)> {
+        self.incoming.lock().await.recv().await
+    }
+}
+
+/// # [`MockChannel`]
+/// The [`Channel`] opened by a [`MockBackend`].
+pub struct MockChannel {
+    /// Produces the responses to requests
+    respond: Arc<Responder>,
+    /// The system the channel is connected to
+    system: String,
+    /// The actor the channel is connected to
+    actor: ActorID,
+    /// The channel's message type
+    message_type: String,
+}
+
+impl Channel for MockChannel {
+    async fn request(&self, data: Vec<u8>) -> Result<Vec<u8>, ChannelError> {
+        (self.respond)(&self.system, &self.actor, &self.message_type, &data)
+    }
+}
+
+/// # [`Captured`]
+/// A single outbound request captured by a [`RecordingBackend`].
+#[derive(Clone, Debug)]
+pub struct Captured {
+    /// The system the request was sent to
+    pub system: String,
+    /// The actor the request was sent to
+    pub actor: ActorID,
+    /// The request's message type
+    pub message_type: String,
+    /// The serialized request
+    pub data: Vec<u8>,
+    /// The serialized response, or [`None`] if the request was sent without waiting for one
+    pub response: Option<Result<Vec<u8>, ChannelError>>,
+}
+
+/// # [`RecordingBackend`]
+/// A [`Backend`] that wraps another backend, and captures every outbound request sent through it.
+pub struct RecordingBackend<B> {
+    /// The backend that actually sends the requests
+    inner: B,
+    /// Every request captured so far, oldest first
+    captured: Arc<Mutex<Vec<Captured>>>,
+}
+
+impl<B> RecordingBackend<B> {
+    /// # [`RecordingBackend::new`]
+    /// Wraps the given backend.
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            captured: Arc::default(),
+        }
+    }
+
+    /// # [`RecordingBackend::inner`]
+    /// Returns the wrapped backend.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// # [`RecordingBackend::captured`]
+    /// Returns every request captured so far, oldest first.
+    pub fn captured(&self) -> Vec<Captured> {
+        self.captured.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// # [`RecordingBackend::clear`]
+    /// Forgets every request captured so far.
+    pub fn clear(&self) {
+        self.captured.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+}
+
+impl<B: Backend> Backend for RecordingBackend<B> {
+    type Channel = RecordingChannel<B::Channel>;
+
+    async fn open_channel<M: Message>(&self, actor: ActorID, system: &str, message_type: &str) -> Result<Self::Channel, OpenChannelError> {
+        Ok(RecordingChannel {
+            inner: self.inner.open_channel::<M>(actor.clone(), system, message_type).await?,
+            captured: self.captured.clone(),
+            system: system.to_string(),
+            actor,
+            message_type: message_type.to_string(),
+        })
+    }
+
+    async fn incoming(&self) -> Option<(ActorID, String, Request)> {
+        self.inner.incoming().await
+    }
+
+    async fn systems(&self) -> Vec<String> {
+        self.inner.systems().await
+    }
+
+    async fn peer_event(&self) -> Option<PeerEvent> {
+        self.inner.peer_event().await
+    }
+}
+
+/// # [`RecordingChannel`]
+/// The [`Channel`] opened by a [`RecordingBackend`].
+pub struct RecordingChannel<C> {
+    /// The channel that actually sends the requests
+    inner: C,
+    /// Where requests are captured to
+    captured: Arc<Mutex<Vec<Captured>>>,
+    /// The system the channel is connected to
+    system: String,
+    /// The actor the channel is connected to
+    actor: ActorID,
+    /// The channel's message type
+    message_type: String,
+}
+
+impl<C> RecordingChannel<C> {
+    /// # [`RecordingChannel::capture`]
+    /// Captures a request and its response.
+    fn capture(&self, data: Vec<u8>, response: Option<Result<Vec<u8>, ChannelError>>) {
+        self.captured.lock().unwrap_or_else(PoisonError::into_inner).push(Captured {
+            system: self.system.clone(),
+            actor: self.actor.clone(),
+            message_type: self.message_type.clone(),
+            data,
+            response,
+        });
+    }
+}
+
+impl<C: Channel> Channel for RecordingChannel<C> {
+    async fn request(&self, data: Vec<u8>) -> Result<Vec<u8>, ChannelError> {
+        let response = self.inner.request(data.clone()).await;
+        self.capture(data, Some(response.clone()));
+        response
+    }
+
+    async fn send_no_reply(&self, data: Vec<u8>) -> Result<(), ChannelError> {
+        let res = self.inner.send_no_reply(data.clone()).await;
+        self.capture(data, None);
+        res
+    }
+
+    async fn request_with_id(&self, data: Vec<u8>, id: u64) -> Result<Vec<u8>, ChannelError> {
+        let response = self.inner.request_with_id(data.clone(), id).await;
+        self.capture(data, Some(response.clone()));
+        response
+    }
+
+    fn is_retryable(&self, error: &ChannelError) -> bool {
+        self.inner.is_retryable(error)
+    }
+}
+
+/// # [`ShutdownGuard`]
+/// Keeps the systems created by [`two_systems`] serving inbound requests, until it is dropped.
+pub struct ShutdownGuard {
+    /// The tasks serving each system
+    serving: JoinSet<()>,
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        self.serving.abort_all();
+    }
+}
+
+/// # [`two_systems`]
+/// Creates two [`Fluxion`] systems with the given ids, each using a [`Palantir`] delegate connected to the other
+/// over a [`MemoryNetwork`], and starts serving inbound requests on both. Actors registered with one system's delegate can be reached from
+/// the other system as foreign actors. Both systems stop serving once the returned [`ShutdownGuard`] is dropped.
+/// 
+/// This has to be called from within a tokio runtime.
+///
+/// # Panics
+/// Panics if either system id is invalid.
+#[must_use]
+pub fn two_systems(a: &str, b: &str) -> (Fluxion<Palantir<MemoryBackend>>, Fluxion<Palantir<MemoryBackend>>, ShutdownGuard) {
+
+    let network = MemoryNetwork::new();
+    let system = |id: &str| Fluxion::new(id, Palantir::new(id.to_string(), network.backend(id)).expect("system ids should be valid"));
+
+    let system_a = system(a);
+    let system_b = system(b);
+
+    let mut serving = JoinSet::new();
+    for system in [system_a.clone(), system_b.clone()] {
+        serving.spawn(async move {
+            system.get_delegate().serve().await;
+        });
+    }
+
+    (system_a, system_b, ShutdownGuard { serving })
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use fluxion::{actor, message, ActorContext, Delegate, Handler, Identifier};
+
+    use crate::{serializer::Pot, PalantirSendError};
+    use super::*;
+
+    #[actor]
+    struct Doubler;
+
+    #[message(u32)]
+    #[derive(Serialize, Deserialize)]
+    struct Double(u32);
+
+    impl Handler<Double> for Doubler {
+        async fn handle_message<D: Delegate>(&self, message: Double, _context: &ActorContext<D>) -> u32 {
+            message.0 * 2
+        }
+    }
+
+    #[tokio::test]
+    async fn between_two_systems() {
+        let (a, b, _guard) = two_systems("a", "b");
+
+        let id = b.add(Doubler).await.unwrap();
+        b.get_delegate().register::<Doubler, Double, _>(b.get_local::<Doubler>(id).await.unwrap()).await;
+
+        let doubler = a.get::<Doubler, Double>(Identifier::Foreign(id, "b")).await.unwrap();
+        assert_eq!(doubler.send(Double(21)).await.unwrap(), 42);
+
+        // Actors that aren't registered can't be reached
+        let missing = a.get::<Doubler, Double>(Identifier::Foreign(id + 1, "b")).await.unwrap();
+        let error = missing.send(Double(21)).await.unwrap_err();
+        assert!(matches!(PalantirSendError::of(&error), Some(PalantirSendError::ActorNotFound)));
+
+        // Neither can actors that were unregistered
+        assert!(b.get_delegate().unregister::<Doubler, Double>(id).await);
+        let error = doubler.send(Double(21)).await.unwrap_err();
+        assert!(matches!(PalantirSendError::of(&error), Some(PalantirSendError::ActorNotFound)));
+    }
+
+    #[tokio::test]
+    async fn mock_responses() {
+        let backend = MockBackend::new(|system, actor, message_type, data| match (system, actor) {
+            ("up", ActorID::Numeric(1)) => Ok([message_type.as_bytes(), data].concat()),
+            _ => Err(ChannelError::HandlerNotFound),
+        });
+
+        let channel = backend.open_channel::<Double>(ActorID::Numeric(1), "up", "double").await.unwrap();
+        assert_eq!(channel.request(vec![1]).await.unwrap(), b"double\x01");
+
+        let channel = backend.open_channel::<Double>(ActorID::Numeric(2), "up", "double").await.unwrap();
+        assert!(matches!(channel.request(vec![1]).await, Err(ChannelError::HandlerNotFound)));
+
+        backend.set_unreachable("up", true);
+        assert!(matches!(backend.open_channel::<Double>(ActorID::Numeric(1), "up", "double").await,
+            Err(OpenChannelError::SystemUnreachable(system)) if system == "up"));
+        backend.set_unreachable("up", false);
+        assert!(backend.open_channel::<Double>(ActorID::Numeric(1), "up", "double").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn mock_injection() {
+        let backend = MockBackend::new(|_, _, _, _| Err(ChannelError::Closed));
+
+        let response = backend.inject(ActorID::Numeric(1), "double", vec![1]);
+        let (actor, message_type, request) = backend.incoming().await.unwrap();
+        assert_eq!((actor, message_type.as_str(), request.data()), (ActorID::Numeric(1), "double", &[1][..]));
+
+        request.respond(Ok(vec![2])).unwrap();
+        assert_eq!(response.await.unwrap().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn mock_serves_palantir() {
+        let palantir = Palantir::new("a".to_string(), MockBackend::new(|_, _, _, _| Err(ChannelError::Closed))).unwrap();
+        let system = Fluxion::new("a", palantir);
+        let id = system.add(Doubler).await.unwrap();
+        system.get_delegate().register::<Doubler, Double, _>(system.get_local::<Doubler>(id).await.unwrap()).await;
+
+        let serving = system.clone();
+        let _serving = tokio::spawn(async move { serving.get_delegate().serve().await });
+
+        let response = system.get_delegate().backend.inject(ActorID::Numeric(id), <Double as fluxion::MessageID>::ID, encode::<Pot>(&Double(21)));
+        assert_eq!(decode::<Pot, u32>(&response.await.unwrap().unwrap()), 42);
+    }
+
+    #[tokio::test]
+    async fn recording() {
+        let backend = RecordingBackend::new(MockBackend::new(|_, _, _, data| match data {
+            [0] => Err(ChannelError::RemoteHandler),
+            data => Ok(data.to_vec()),
+        }));
+
+        let channel = backend.open_channel::<Double>(ActorID::Numeric(1), "b", "double").await.unwrap();
+        channel.request(vec![1]).await.unwrap();
+        channel.request(vec![0]).await.unwrap_err();
+        channel.send_no_reply(vec![2]).await.unwrap();
+        channel.request_with_id(vec![3], 7).await.unwrap();
+
+        let captured = backend.captured();
+        assert_eq!(captured.len(), 4);
+        assert!(captured.iter().all(|c| c.system == "b" && c.actor == ActorID::Numeric(1) && c.message_type == "double"));
+        assert_eq!(captured.iter().map(|c| c.data.clone()).collect::<Vec<_>>(), [[1], [0], [2], [3]]);
+        assert!(matches!(&captured[0].response, Some(Ok(data)) if *data == [1]));
+        assert!(matches!(captured[1].response, Some(Err(ChannelError::RemoteHandler))));
+        assert!(captured[2].response.is_none());
+
+        backend.clear();
+        assert!(backend.captured().is_empty());
+    }
+
+    #[tokio::test]
+    async fn recording_palantir() {
+        let backend = RecordingBackend::new(MockBackend::new(|_, _, _, _| Ok(encode::<Pot>(&42u32))));
+        let system = Fluxion::new("a", Palantir::new("a".to_string(), backend).unwrap());
+
+        let doubler = system.get::<Doubler, Double>(Identifier::Foreign(1, "b")).await.unwrap();
+        assert_eq!(doubler.send(Double(21)).await.unwrap(), 42);
+
+        let captured = system.get_delegate().backend.captured();
+        assert_eq!(captured.len(), 1);
+        assert_eq!(decode::<Pot, Double>(&captured[0].data).0, 21);
+    }
+}
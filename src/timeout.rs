@@ -0,0 +1,46 @@
+//! # Timeout
+//! By default, a sender waits for a response for as long as it takes. With [`Palantir::set_request_timeout`](crate::Palantir::set_request_timeout)
+//! every sender instead gives up after a while, and [`Palantir::set_message_timeout`](crate::Palantir::set_message_timeout)
+//! overrides that for a single message type. A request that times out fails with a [`SendTimeout`] (see [`PalantirSendError::Timeout`](crate::PalantirSendError::Timeout)).
+
+use std::{collections::HashMap, time::Duration};
+
+use thiserror::Error;
+
+use crate::ActorID;
+
+
+
+/// # [`SendTimeout`]
+/// A request wasn't responded to within its timeout.
+/// The request fails with a [`PalantirSendError::Timeout`](crate::PalantirSendError::Timeout), and the actor may still handle the message.
+#[derive(Clone, Debug, Error)]
+#[error("request {message_type} to {actor:?} on {system} timed out after {timeout:?}")]
+pub struct SendTimeout {
+    /// The system the request was sent to
+    pub system: String,
+    /// The actor the request was sent to
+    pub actor: ActorID,
+    /// The request's message type
+    pub message_type: &'static str,
+    /// How long the request waited for a response
+    pub timeout: Duration,
+}
+
+/// # [`Timeouts`]
+/// The request timeouts of a palantir instance.
+#[derive(Default)]
+pub(crate) struct Timeouts {
+    /// The timeout of message types without an override, or [`None`] if they wait forever
+    pub default: Option<Duration>,
+    /// The timeouts of individual message types
+    pub overrides: HashMap<String, Duration>,
+}
+
+impl Timeouts {
+    /// # [`Timeouts::get`]
+    /// Returns the timeout of the given message type, or [`None`] if it waits forever.
+    pub fn get(&self, message_type: &str) -> Option<Duration> {
+        self.overrides.get(message_type).copied().or(self.default)
+    }
+}
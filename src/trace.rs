@@ -0,0 +1,45 @@
+//! # Trace
+//! Palantir reports what it is doing through [`tracing`](https://docs.rs/tracing) when the `tracing` feature is enabled,
+//! which it is by default. Connections, channels, and requests each get their own span.
+//!
+//! This module re-exports the parts of `tracing` that palantir uses, and replaces them with no-ops if the feature is disabled,
+//! so that the rest of the crate doesn't need to care which it is.
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, debug_span, info, info_span, warn, Instrument};
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use noop::{debug, debug_span, info, info_span, warn, Instrument, Span};
+
+#[cfg(not(feature = "tracing"))]
+mod noop {
+    /// # [`Span`]
+    /// Stands in for [`tracing::Span`], and records nothing.
+    pub struct Span;
+
+    /// # [`Instrument`]
+    /// Stands in for `tracing::Instrument`, and leaves the future as is.
+    pub trait Instrument: Sized {
+        /// # [`Instrument::instrument`]
+        /// Returns the future unchanged.
+        fn instrument(self, _span: Span) -> Self {
+            self
+        }
+    }
+
+    impl<F: std::future::Future> Instrument for F {}
+
+    /// # [`event`]
+    /// Stands in for the event macros, and discards the event without evaluating it.
+    macro_rules! event {
+        ($($arg:tt)*) => {};
+    }
+
+    /// # [`span`]
+    /// Stands in for the span macros, and creates a [`Span`] without evaluating its fields.
+    macro_rules! span {
+        ($($arg:tt)*) => { $crate::trace::Span };
+    }
+
+    pub(crate) use {event as debug, event as info, event as warn, span as debug_span, span as info_span};
+}
@@ -0,0 +1,151 @@
+//! # TTL
+//! Requests can be sent with a time to live using [`Palantir::send_with_ttl`](crate::Palantir::send_with_ttl).
+//! A request that is still waiting to be sent once its time to live has passed is dropped locally, and one that arrives
+//! after it has passed is discarded by the receiving system instead of being handled. Both are reported to the sender as
+//! [`ChannelError::Expired`](crate::backend::ChannelError::Expired), so that stale work is never executed after delays or outages.
+//!
+//! Deadlines are sent as wall clock times, so they are only as accurate as the clocks of the two systems are in sync.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use fluxion::{Message, MessageID};
+use serde::{Deserialize, Serialize};
+
+
+
+/// # [`Expiring`]
+/// A request that should be discarded if it hasn't been handled by its deadline, which should be handled as a request of the given message type.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Expiring {
+    /// When the request expires, in milliseconds since the unix epoch
+    pub deadline: u64,
+    /// The request's actual message type
+    pub message_type: String,
+    /// The serialized message
+    pub data: Vec<u8>,
+}
+
+impl Message for Expiring {
+    /// The response, still serialized
+    type Result = Vec<u8>;
+}
+
+impl MessageID for Expiring {
+    const ID: &'static str = "palantir::ttl::Expiring";
+}
+
+/// # [`now`]
+/// Returns the current wall clock time, in milliseconds since the unix epoch.
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map_or(0, |since| u64::try_from(since.as_millis()).unwrap_or(u64::MAX))
+}
+
+/// # [`deadline_after`]
+/// Returns the deadline of a request sent now with the given time to live.
+pub(crate) fn deadline_after(ttl: Duration) -> u64 {
+    now().saturating_add(u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX))
+}
+
+/// # [`is_expired`]
+/// Checks whether the given deadline has passed.
+pub(crate) fn is_expired(deadline: u64) -> bool {
+    now() >= deadline
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use fluxion::{actor, message, ActorContext, Delegate, Fluxion, Handler};
+
+    use crate::{backend::ChannelError, serializer::Pot, testkit::{decode, encode, two_systems, MockBackend}, ActorID, Palantir, PalantirSendError};
+    use super::*;
+
+    #[actor]
+    struct Sleeper;
+
+    /// Sleeps for the given number of milliseconds, and returns them
+    #[message(u64)]
+    #[derive(Serialize, Deserialize)]
+    struct Sleep(u64);
+
+    impl Handler<Sleep> for Sleeper {
+        async fn handle_message<D: Delegate>(&self, message: Sleep, _context: &ActorContext<D>) -> u64 {
+            tokio::time::sleep(Duration::from_millis(message.0)).await;
+            message.0
+        }
+    }
+
+    fn is_expired_error(error: &fluxion::MessageSendError) -> bool {
+        matches!(PalantirSendError::of(error), Some(PalantirSendError::Transport(ChannelError::Expired)))
+    }
+
+    #[test]
+    fn deadlines() {
+        assert!(is_expired(deadline_after(Duration::ZERO)));
+        assert!(!is_expired(deadline_after(Duration::from_secs(60))));
+    }
+
+    #[tokio::test]
+    async fn handled_within_ttl() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Sleeper).await.unwrap();
+        b.get_delegate().register::<Sleeper, Sleep, _>(b.get_local::<Sleeper>(id).await.unwrap()).await;
+
+        let response = a.get_delegate().send_with_ttl("b", ActorID::Numeric(id), Duration::from_secs(5), Sleep(1)).await.unwrap();
+        assert_eq!(response, 1);
+    }
+
+    #[tokio::test]
+    async fn sender_stops_waiting() {
+        let (a, b, _guard) = two_systems("a", "b");
+        let id = b.add(Sleeper).await.unwrap();
+        b.get_delegate().register::<Sleeper, Sleep, _>(b.get_local::<Sleeper>(id).await.unwrap()).await;
+
+        let start = Instant::now();
+        let error = a.get_delegate().send_with_ttl("b", ActorID::Numeric(id), Duration::from_millis(50), Sleep(1000)).await.unwrap_err();
+        assert!(is_expired_error(&error));
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn sender_stops_opening() {
+        let backend = MockBackend::new(|_, _, _, _| Ok(encode::<Pot>(&0u64)));
+        backend.set_unreachable("b", true);
+        let a = Fluxion::new("a", Palantir::new("a".to_string(), backend).unwrap());
+
+        // Reopening backs off for longer than the time to live
+        let start = Instant::now();
+        let error = a.get_delegate().send_with_ttl("b", ActorID::Numeric(1), Duration::from_millis(20), Sleep(0)).await.unwrap_err();
+        assert!(is_expired_error(&error));
+        assert!(start.elapsed() < Duration::from_millis(120));
+    }
+
+    #[tokio::test]
+    async fn receiver_discards_expired() {
+        let a = Fluxion::new("a", Palantir::new("a".to_string(), MockBackend::new(|_, _, _, _| Err(ChannelError::Closed))).unwrap());
+        let id = a.add(Sleeper).await.unwrap();
+        a.get_delegate().register::<Sleeper, Sleep, _>(a.get_local::<Sleeper>(id).await.unwrap()).await;
+
+        let serving = a.clone();
+        let _serving = tokio::spawn(async move { serving.get_delegate().serve().await });
+
+        let expiring = |deadline| encode::<Pot>(&Expiring {
+            deadline,
+            message_type: <Sleep as MessageID>::ID.to_string(),
+            data: encode::<Pot>(&Sleep(0)),
+        });
+
+        let backend = &a.get_delegate().backend;
+        let response = backend.inject(ActorID::Numeric(id), Expiring::ID, expiring(deadline_after(Duration::from_secs(60))));
+        assert_eq!(decode::<Pot, u64>(&response.await.unwrap().unwrap()), 0);
+
+        let response = backend.inject(ActorID::Numeric(id), Expiring::ID, expiring(now() - 1));
+        assert!(matches!(response.await.unwrap(), Err(ChannelError::Expired)));
+        let stats = a.get_delegate().stats();
+        assert_eq!(stats[&(id, <Sleep as MessageID>::ID.to_string())].handled, 1);
+    }
+}